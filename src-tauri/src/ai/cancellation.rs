@@ -0,0 +1,107 @@
+use crate::ai::traits::{AIProviderError, ChatCompletionStream};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Run `future` unless `token` is cancelled first. Cancelling drops `future`, and with it any
+/// HTTP request, retry wait or stream it has open, so every provider stops promptly.
+pub async fn cancellable<T, F>(token: &CancellationToken, future: F) -> Result<T, AIProviderError>
+where
+    F: Future<Output = Result<T, AIProviderError>>,
+{
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(AIProviderError::Cancelled),
+        result = future => result,
+    }
+}
+
+/// End `stream` with a `Cancelled` error as soon as `token` is cancelled, dropping the provider's stream
+pub fn cancellable_stream(token: CancellationToken, stream: ChatCompletionStream) -> ChatCompletionStream {
+    Box::pin(futures::stream::unfold(Some((token, stream)), |state| async move {
+        let (token, mut stream) = state?;
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => Some((Err(AIProviderError::Cancelled), None)),
+            item = stream.next() => item.map(|item| (item, Some((token, stream)))),
+        }
+    }))
+}
+
+/// Cancellation tokens for the requests in flight, keyed by request id
+#[derive(Debug, Default)]
+pub struct InFlightRequests {
+    tokens: Mutex<HashMap<String, CancellationToken>>,
+}
+
+impl InFlightRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `request_id`. It stays cancellable until the returned handle is dropped.
+    pub fn start(&self, request_id: &str) -> Result<InFlightRequest<'_>, AIProviderError> {
+        let mut tokens = self.tokens.lock().unwrap();
+        if tokens.contains_key(request_id) {
+            return Err(AIProviderError::InvalidRequest(format!("Request {} is already running", request_id)));
+        }
+
+        let token = CancellationToken::new();
+        tokens.insert(request_id.to_string(), token.clone());
+        Ok(InFlightRequest {
+            requests: self,
+            request_id: request_id.to_string(),
+            token,
+        })
+    }
+
+    /// Cancel `request_id`. Returns false when no such request is running, e.g. it already finished.
+    pub fn cancel(&self, request_id: &str) -> bool {
+        match self.tokens.lock().unwrap().get(request_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Ids of the requests still running
+    pub fn request_ids(&self) -> Vec<String> {
+        self.tokens.lock().unwrap().keys().cloned().collect()
+    }
+}
+
+/// A registered request; unregisters itself when dropped
+#[derive(Debug)]
+pub struct InFlightRequest<'a> {
+    requests: &'a InFlightRequests,
+    request_id: String,
+    token: CancellationToken,
+}
+
+impl InFlightRequest<'_> {
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Run `future` as this request, so `cancel` can stop it
+    pub async fn run<T, F>(&self, future: F) -> Result<T, AIProviderError>
+    where
+        F: Future<Output = Result<T, AIProviderError>>,
+    {
+        cancellable(&self.token, future).await
+    }
+}
+
+impl Drop for InFlightRequest<'_> {
+    fn drop(&mut self) {
+        self.requests.tokens.lock().unwrap().remove(&self.request_id);
+    }
+}
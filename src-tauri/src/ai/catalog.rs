@@ -0,0 +1,203 @@
+use crate::ai::models::{AIModel, ModelCapability, ModelPricing};
+use chrono::NaiveDate;
+use lazy_static::lazy_static;
+use serde::{Serialize, Deserialize};
+use std::sync::{Arc, RwLock};
+
+/// The catalog shipped with the app; see the file for how models are matched
+const BUNDLED_CATALOG: &str = include_str!("model_catalog.yaml");
+
+lazy_static! {
+    static ref MODEL_CATALOG: RwLock<Arc<ModelCatalog>> = RwLock::new(Arc::new(ModelCatalog::bundled()));
+}
+
+/// What we know about a model, or a family of models sharing an id prefix
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogEntry {
+    pub id: String,
+    /// Other names the model goes by on some servers, matched the same way as `id`
+    pub aliases: Vec<String>,
+    pub display_name: Option<String>,
+    pub context_window: Option<usize>,
+    pub max_output_tokens: Option<usize>,
+    pub capabilities: Option<Vec<ModelCapability>>,
+    pub pricing: Option<ModelPricing>,
+    /// When the provider retires the model
+    pub deprecation_date: Option<NaiveDate>,
+}
+
+impl CatalogEntry {
+    /// Layer `overrides` on top of this entry; any field set there wins
+    fn merged_with(&self, overrides: &CatalogEntry) -> CatalogEntry {
+        let overrides = overrides.clone();
+        let mut aliases = self.aliases.clone();
+        aliases.extend(overrides.aliases.into_iter().filter(|alias| !self.aliases.contains(alias)));
+        CatalogEntry {
+            id: self.id.clone(),
+            aliases,
+            display_name: overrides.display_name.or_else(|| self.display_name.clone()),
+            context_window: overrides.context_window.or(self.context_window),
+            max_output_tokens: overrides.max_output_tokens.or(self.max_output_tokens),
+            capabilities: overrides.capabilities.or_else(|| self.capabilities.clone()),
+            pricing: overrides.pricing.or_else(|| self.pricing.clone()),
+            deprecation_date: overrides.deprecation_date.or(self.deprecation_date),
+        }
+    }
+
+    /// How much of `model_id` this entry covers, when it matches at all
+    fn match_length(&self, model_id: &str) -> Option<usize> {
+        std::iter::once(&self.id)
+            .chain(&self.aliases)
+            .map(|name| name.to_lowercase())
+            .filter(|name| {
+                model_id == name || model_id.strip_prefix(name.as_str())
+                    .is_some_and(|rest| rest.starts_with(['-', ':', '@']))
+            })
+            .map(|name| name.len())
+            .max()
+    }
+}
+
+/// The catalog data file: the bundled one, or the shape of user overrides
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CatalogFile {
+    pub version: u32,
+    #[serde(default)]
+    pub updated: Option<String>,
+    pub models: Vec<CatalogEntry>,
+}
+
+/// Model facts providers don't report: context windows, output limits, capabilities, prices and
+/// retirement dates. Bundled entries fill in what a provider left out; user overrides win over
+/// everything, so a local model's real window can be set by hand.
+#[derive(Debug, Clone, Default)]
+pub struct ModelCatalog {
+    version: u32,
+    entries: Vec<CatalogEntry>,
+    overrides: Vec<CatalogEntry>,
+}
+
+/// Lowercase, without the "openai/" of OpenRouter or the "models/" of Gemini
+fn normalize_model_id(model_id: &str) -> String {
+    model_id.rsplit('/').next().unwrap_or(model_id).to_lowercase()
+}
+
+/// The entry covering the most of `model_id`
+fn find<'a>(entries: &'a [CatalogEntry], model_id: &str) -> Option<&'a CatalogEntry> {
+    let model_id = normalize_model_id(model_id);
+    entries.iter()
+        .filter_map(|entry| entry.match_length(&model_id).map(|length| (length, entry)))
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, entry)| entry)
+}
+
+impl ModelCatalog {
+    /// The catalog shipped with the app
+    pub fn bundled() -> Self {
+        let file: CatalogFile = serde_yaml::from_str(BUNDLED_CATALOG)
+            .expect("The bundled model catalog is invalid");
+        ModelCatalog { version: file.version, entries: file.models, overrides: Vec::new() }
+    }
+
+    /// The bundled catalog with the user's overrides. Overrides with the id of a bundled entry
+    /// change just the fields they set; others add models.
+    pub fn with_overrides(overrides: Vec<CatalogEntry>) -> Self {
+        ModelCatalog { overrides, ..Self::bundled() }
+    }
+
+    /// The catalog `list_models` uses
+    pub fn global() -> Arc<ModelCatalog> {
+        MODEL_CATALOG.read().unwrap().clone()
+    }
+
+    /// Replace the catalog `list_models` uses, e.g. after the user edits their overrides
+    pub fn install(catalog: ModelCatalog) {
+        *MODEL_CATALOG.write().unwrap() = Arc::new(catalog);
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn overrides(&self) -> &[CatalogEntry] {
+        &self.overrides
+    }
+
+    /// Every entry, overrides merged in
+    pub fn entries(&self) -> Vec<CatalogEntry> {
+        let mut entries: Vec<CatalogEntry> = self.entries.iter()
+            .map(|entry| match self.overrides.iter().find(|o| o.id.eq_ignore_ascii_case(&entry.id)) {
+                Some(overrides) => entry.merged_with(overrides),
+                None => entry.clone(),
+            })
+            .collect();
+        entries.extend(self.overrides.iter()
+            .filter(|o| !self.entries.iter().any(|entry| entry.id.eq_ignore_ascii_case(&o.id)))
+            .cloned());
+        entries
+    }
+
+    /// What the catalog says about `model_id`, overrides merged in
+    pub fn lookup(&self, model_id: &str) -> Option<CatalogEntry> {
+        let bundled = find(&self.entries, model_id);
+        let overrides = find(&self.overrides, model_id);
+        match (bundled, overrides) {
+            (Some(entry), Some(overrides)) => Some(entry.merged_with(overrides)),
+            (entry, overrides) => entry.or(overrides).cloned(),
+        }
+    }
+
+    /// Fill in `model` from the catalog. A context window the provider reported is kept, as it
+    /// may reflect how the server is set up; the catalog's capabilities replace the provider's
+    /// guesses. Overrides replace whatever they set.
+    pub fn apply(&self, model: &mut AIModel) {
+        self.apply_with(model, true);
+    }
+
+    /// Like `apply`, for providers that report what each model can do: the catalog's
+    /// capabilities are only used when the provider reported none
+    pub fn apply_to_reported(&self, model: &mut AIModel) {
+        let replace_capabilities = model.capabilities.is_empty();
+        self.apply_with(model, replace_capabilities);
+    }
+
+    fn apply_with(&self, model: &mut AIModel, replace_capabilities: bool) {
+        if let Some(entry) = find(&self.entries, &model.id) {
+            fill(model, entry, replace_capabilities);
+        }
+        if let Some(overrides) = find(&self.overrides, &model.id) {
+            force(model, overrides);
+        }
+    }
+}
+
+fn fill(model: &mut AIModel, entry: &CatalogEntry, replace_capabilities: bool) {
+    if model.name == model.id {
+        if let Some(display_name) = &entry.display_name {
+            model.name = display_name.clone();
+        }
+    }
+    if let Some(capabilities) = entry.capabilities.as_ref().filter(|_| replace_capabilities) {
+        model.capabilities = capabilities.clone();
+    }
+    model.context_length = model.context_length.or(entry.context_window);
+    model.max_output_tokens = model.max_output_tokens.or(entry.max_output_tokens);
+    model.pricing = model.pricing.take().or_else(|| entry.pricing.clone());
+    model.deprecation_date = model.deprecation_date.or(entry.deprecation_date);
+}
+
+fn force(model: &mut AIModel, overrides: &CatalogEntry) {
+    if let Some(display_name) = &overrides.display_name {
+        model.name = display_name.clone();
+    }
+    if let Some(capabilities) = &overrides.capabilities {
+        model.capabilities = capabilities.clone();
+    }
+    model.context_length = overrides.context_window.or(model.context_length);
+    model.max_output_tokens = overrides.max_output_tokens.or(model.max_output_tokens);
+    if let Some(pricing) = &overrides.pricing {
+        model.pricing = Some(pricing.clone());
+    }
+    model.deprecation_date = overrides.deprecation_date.or(model.deprecation_date);
+}
@@ -1,10 +1,14 @@
 pub mod traits;
 pub mod models;
 pub mod providers;
+pub mod cancellation;
+pub mod catalog;
+pub mod model_cache;
+pub mod resolver;
 
 // Re-export the most important types for convenience
 // This lets users write `use crate::ai::AIModel` instead of `use crate::ai::models::AIModel`
-pub use traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, AIProviderError};
+pub use traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, AIProviderError, HealthStatus};
 pub use models::{
     AIModel,
     ChatMessage, 
@@ -18,4 +22,8 @@ pub use providers::openai_provider::OpenAIProvider;
 
 pub use self::models::*;
 pub use self::providers::*;
-pub use self::traits::*;
\ No newline at end of file
+pub use self::traits::*;
+pub use self::cancellation::{cancellable, cancellable_stream, InFlightRequests, InFlightRequest};
+pub use self::catalog::{ModelCatalog, CatalogEntry, CatalogFile};
+pub use self::model_cache::{ModelCache, ProviderModels, ListedModel};
+pub use self::resolver::{ModelResolver, ModelPreferences, ModelSource, ResolvedModel};
\ No newline at end of file
@@ -0,0 +1,234 @@
+use crate::ai::models::AIModel;
+use crate::ai::traits::{AIErrorKind, AIProviderError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// How long a listing is served before it's refreshed in the background
+pub const DEFAULT_MODEL_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// One provider's models, as of its last listing
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderModels {
+    /// The registered provider id, e.g. "Ollama"
+    pub provider_id: String,
+    /// Name of the saved endpoint, for OpenAI compatible providers
+    pub endpoint: Option<String>,
+    /// False when the provider didn't answer the last listing. Its models are then the ones it
+    /// listed before, if any.
+    pub available: bool,
+    pub error: Option<String>,
+    pub error_kind: Option<AIErrorKind>,
+    pub models: Vec<AIModel>,
+    /// When `models` was listed
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+impl ProviderModels {
+    /// The outcome of asking a provider for its models
+    pub fn from_result(provider_id: &str, endpoint: Option<&str>, result: Result<Vec<AIModel>, AIProviderError>) -> Self {
+        let (available, error, error_kind, models, fetched_at) = match result {
+            Ok(models) => (true, None, None, models, Some(Utc::now())),
+            Err(e) => (false, Some(e.to_string()), Some(e.kind()), Vec::new(), None),
+        };
+        ProviderModels {
+            provider_id: provider_id.to_string(),
+            endpoint: endpoint.map(str::to_string),
+            available,
+            error,
+            error_kind,
+            models,
+            fetched_at,
+        }
+    }
+
+    fn same_target(&self, other: &ProviderModels) -> bool {
+        self.provider_id == other.provider_id && self.endpoint == other.endpoint
+    }
+
+    /// Each model, tagged with where it came from
+    pub fn listed(&self) -> impl Iterator<Item = ListedModel> + '_ {
+        self.models.iter().map(|model| ListedModel {
+            provider_id: self.provider_id.clone(),
+            endpoint: self.endpoint.clone(),
+            available: self.available,
+            model: model.clone(),
+        })
+    }
+}
+
+/// A model in the merged listing of every provider
+#[derive(Debug, Clone, Serialize)]
+pub struct ListedModel {
+    /// The registered provider id to pass to transform_text
+    pub provider_id: String,
+    pub endpoint: Option<String>,
+    /// Whether its provider answered the last listing
+    pub available: bool,
+    #[serde(flatten)]
+    pub model: AIModel,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    listings: Vec<ProviderModels>,
+    refreshed_at: Option<Instant>,
+    invalidated: bool,
+    refreshing: bool,
+}
+
+/// The last listing of every provider's models. Listings older than the TTL are still served,
+/// so the model picker never waits, while a background refresh brings them up to date.
+#[derive(Debug)]
+pub struct ModelCache {
+    ttl: Duration,
+    state: Mutex<CacheState>,
+    /// Woken whenever a refresh ends, however it ended
+    refresh_ended: Notify,
+}
+
+impl Default for ModelCache {
+    fn default() -> Self {
+        ModelCache::new(DEFAULT_MODEL_CACHE_TTL)
+    }
+}
+
+impl ModelCache {
+    pub fn new(ttl: Duration) -> Self {
+        ModelCache { ttl, state: Mutex::new(CacheState::default()), refresh_ended: Notify::new() }
+    }
+
+    /// The cached listings and whether they are still fresh; None before the first refresh
+    pub fn get(&self) -> Option<(Vec<ProviderModels>, bool)> {
+        let state = self.state.lock().unwrap();
+        let refreshed_at = state.refreshed_at?;
+        let fresh = !state.invalidated && refreshed_at.elapsed() < self.ttl;
+        Some((state.listings.clone(), fresh))
+    }
+
+    /// Claim the refresh. Returns false when one is already running. The claim is given up with
+    /// `finish_refresh` or `abort_refresh`.
+    pub fn begin_refresh(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        !std::mem::replace(&mut state.refreshing, true)
+    }
+
+    /// Claim the refresh, waiting for a running one to end first
+    pub async fn wait_to_refresh(&self) {
+        loop {
+            // Registered before trying, so the end of a refresh can't slip between the two
+            let ended = self.refresh_ended.notified();
+            if self.begin_refresh() {
+                return;
+            }
+            ended.await;
+        }
+    }
+
+    /// Give up the refresh without storing anything, e.g. when the providers couldn't be read
+    pub fn abort_refresh(&self) {
+        self.state.lock().unwrap().refreshing = false;
+        self.refresh_ended.notify_waiters();
+    }
+
+    /// Store a new listing. A provider that didn't answer keeps the models it listed last
+    /// time, marked unavailable.
+    pub fn finish_refresh(&self, listings: Vec<ProviderModels>) -> Vec<ProviderModels> {
+        let mut state = self.state.lock().unwrap();
+        let listings: Vec<ProviderModels> = listings.into_iter()
+            .map(|mut listing| {
+                if !listing.available {
+                    if let Some(previous) = state.listings.iter().find(|previous| previous.same_target(&listing)) {
+                        listing.models = previous.models.clone();
+                        listing.fetched_at = previous.fetched_at;
+                    }
+                }
+                listing
+            })
+            .collect();
+
+        state.listings = listings.clone();
+        state.refreshed_at = Some(Instant::now());
+        state.invalidated = false;
+        state.refreshing = false;
+        self.refresh_ended.notify_waiters();
+        listings
+    }
+
+    /// Mark the listings stale, e.g. after a model was pulled or a provider's settings changed
+    pub fn invalidate(&self) {
+        self.state.lock().unwrap().invalidated = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn model(id: &str) -> AIModel {
+        AIModel {
+            id: id.to_string(),
+            name: id.to_string(),
+            provider: "ollama".to_string(),
+            capabilities: Vec::new(),
+            context_length: None,
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn aborted_refresh_can_be_claimed_again() {
+        let cache = ModelCache::default();
+        assert!(cache.begin_refresh());
+        assert!(!cache.begin_refresh());
+
+        cache.abort_refresh();
+        assert!(cache.get().is_none());
+        assert!(cache.begin_refresh());
+    }
+
+    #[tokio::test]
+    async fn waiting_refresh_claims_once_the_running_one_ends() {
+        let cache = Arc::new(ModelCache::default());
+        assert!(cache.begin_refresh());
+
+        let waiting = tokio::spawn({
+            let cache = cache.clone();
+            async move { cache.wait_to_refresh().await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        cache.finish_refresh(Vec::new());
+        tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+        // The waiter holds the claim now
+        assert!(!cache.begin_refresh());
+    }
+
+    #[test]
+    fn unavailable_provider_keeps_its_last_models() {
+        let cache = ModelCache::default();
+        cache.begin_refresh();
+        cache.finish_refresh(vec![ProviderModels::from_result("Ollama", None, Ok(vec![model("llama3.1:8b")]))]);
+
+        cache.invalidate();
+        let (_, fresh) = cache.get().unwrap();
+        assert!(!fresh);
+
+        cache.begin_refresh();
+        let error = AIProviderError::Provider(Box::new(crate::ai::traits::ProviderErrorDetails::new(AIErrorKind::Network, "Ollama", "Connection refused")));
+        let listings = cache.finish_refresh(vec![ProviderModels::from_result("Ollama", None, Err(error))]);
+
+        assert!(!listings[0].available);
+        assert_eq!(listings[0].error_kind, Some(AIErrorKind::Network));
+        assert_eq!(listings[0].models[0].id, "llama3.1:8b");
+        assert!(listings[0].fetched_at.is_some());
+        assert!(cache.get().unwrap().1);
+    }
+}
@@ -8,9 +8,27 @@ pub struct AIModel {
     pub provider: String,
     pub capabilities: Vec<ModelCapability>,
     pub context_length: Option<usize>,
+    /// Most tokens one response can have
+    #[serde(default)]
+    pub max_output_tokens: Option<usize>,
+    #[serde(default)]
+    pub pricing: Option<ModelPricing>,
+    /// When the provider retires the model
+    #[serde(default)]
+    pub deprecation_date: Option<chrono::NaiveDate>,
     pub additional_info: serde_json::Value,
 }
 
+/// List prices in USD per million tokens
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    /// Input served from the provider's prompt cache, where that's cheaper
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum ModelCapability {
     Completion,
@@ -22,7 +40,7 @@ pub enum ModelCapability {
 }
 
 /// Standardized message structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: MessageRole,
     pub content: String,
@@ -39,13 +57,19 @@ pub enum MessageRole {
 }
 
 /// Standardized chat completion request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatCompletionRequest {
     pub messages: Vec<ChatMessage>,
     pub model: String,
     pub temperature: Option<f32>,
     pub max_tokens: Option<u32>,
     pub stream: bool,
+    #[serde(default)]
+    pub top_p: Option<f32>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    #[serde(default)]
+    pub seed: Option<i64>,
     // Add other common parameters
 }
 
@@ -71,6 +95,8 @@ pub struct ChatCompletionChunk {
     pub id: String,
     pub choices: Vec<ChatCompletionChunkChoice>,
     pub created: u64,
+    /// Only present on the final chunk, when the provider reports it
+    pub usage: Option<TokenUsage>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
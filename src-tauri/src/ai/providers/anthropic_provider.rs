@@ -0,0 +1,619 @@
+use crate::ai::{
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, AIErrorKind, ProviderErrorDetails, ChatCompletionStream},
+    models::*,
+};
+use async_trait::async_trait;
+use futures::{future, StreamExt};
+use reqwest::{Client as HttpClient, header};
+use serde::{Serialize, Deserialize};
+use super::sse::sse_data_stream;
+use super::connection::ConnectionSettings;
+use super::http_error::{check_response, classify};
+
+/// Anthropic's public API endpoint
+pub const ANTHROPIC_DEFAULT_URL: &str = "https://api.anthropic.com/v1";
+
+/// Messages API version sent with every request
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Name used in errors
+const PROVIDER_NAME: &str = "Anthropic";
+
+/// The Messages API requires `max_tokens`, so this is used when the request leaves it out
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic Messages API provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicProvider {
+    #[serde(skip)]
+    client: HttpClient,
+    #[serde(skip)]
+    api_key: String,
+    base_url: String,
+    preferred_model_name: Option<String>,
+    /// Kept so the client can be rebuilt when the connection settings change
+    #[serde(skip)]
+    default_headers: header::HeaderMap,
+    #[serde(skip)]
+    connection: ConnectionSettings,
+}
+
+impl AnthropicProvider {
+    /// Create a new Anthropic provider talking to the public API
+    pub fn new(api_key: &str) -> Self {
+        Self::with_base_url(api_key, ANTHROPIC_DEFAULT_URL)
+    }
+
+    /// Create a provider for another base URL, such as a proxy or a local mock server
+    pub fn with_base_url(api_key: &str, base_url: &str) -> Self {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            "anthropic-version",
+            header::HeaderValue::from_static(ANTHROPIC_VERSION),
+        );
+
+        let connection = ConnectionSettings::default();
+        let client = connection.build_client(headers.clone())
+            .expect("Failed to create HTTP client");
+
+        AnthropicProvider {
+            client,
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            preferred_model_name: None,
+            default_headers: headers,
+            connection,
+        }
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.client = connection.build_client(self.default_headers.clone())?;
+        self.connection = connection;
+        Ok(())
+    }
+
+    fn add_auth_header(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder.header("x-api-key", &self.api_key)
+    }
+
+    /// POST to /messages and hand back the response if the status is good
+    async fn send_messages_request(&self, body: &AnthropicRequest) -> Result<reqwest::Response, AIProviderError> {
+        let url = format!("{}/messages", self.base_url);
+        log::debug!("Anthropic request URL: {}", url);
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.post(&url).json(body))).await?;
+
+        check_response(PROVIDER_NAME, response).await
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct AnthropicMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+struct AnthropicRequest {
+    model: String,
+    messages: Vec<AnthropicMessage>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+impl AnthropicRequest {
+    /// System messages move to the top level `system` field; the rest keep their order
+    fn from_request(request: &ChatCompletionRequest, stream: bool) -> Result<Self, AIProviderError> {
+        let mut system_parts = Vec::new();
+        let mut messages = Vec::new();
+
+        for msg in &request.messages {
+            match msg.role {
+                MessageRole::System => system_parts.push(msg.content.clone()),
+                MessageRole::User => messages.push(AnthropicMessage {
+                    role: "user".to_string(),
+                    content: msg.content.clone(),
+                }),
+                MessageRole::Assistant => messages.push(AnthropicMessage {
+                    role: "assistant".to_string(),
+                    content: msg.content.clone(),
+                }),
+                MessageRole::Tool | MessageRole::Function => {
+                    return Err(AIProviderError::InvalidRequest(
+                        "Tool and Function messages are not supported yet".to_string()
+                    ));
+                }
+            }
+        }
+
+        Ok(AnthropicRequest {
+            model: request.model.clone(),
+            messages,
+            max_tokens: request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            system: if system_parts.is_empty() { None } else { Some(system_parts.join("\n\n")) },
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop_sequences: request.stop.clone(),
+            stream,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicErrorDetail {
+    #[serde(rename = "type")]
+    error_type: String,
+    message: String,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<AnthropicUsage> for TokenUsage {
+    fn from(usage: AnthropicUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.input_tokens + usage.output_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicContentBlock {
+    Text { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    id: String,
+    model: String,
+    content: Vec<AnthropicContentBlock>,
+    stop_reason: Option<String>,
+    usage: AnthropicUsage,
+}
+
+#[derive(Deserialize)]
+struct AnthropicStreamMessage {
+    id: String,
+    #[serde(default)]
+    usage: AnthropicUsage,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicDelta {
+    TextDelta { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicMessageDelta {
+    stop_reason: Option<String>,
+}
+
+// The server-sent events of a streaming Messages call
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicStreamEvent {
+    MessageStart { message: AnthropicStreamMessage },
+    // Every text block streams into the one choice, so the block's index isn't needed
+    ContentBlockDelta { delta: AnthropicDelta },
+    MessageDelta { delta: AnthropicMessageDelta, #[serde(default)] usage: AnthropicUsage },
+    Error { error: AnthropicErrorDetail },
+    // content_block_start, content_block_stop, message_stop and ping carry nothing we need
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Serialize)]
+struct AnthropicModelData {
+    id: String,
+    display_name: Option<String>,
+    created_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelList {
+    data: Vec<AnthropicModelData>,
+}
+
+/// Map Anthropic's stop reasons onto the OpenAI style ones the rest of the app expects
+fn convert_stop_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "end_turn" | "stop_sequence" => "stop".to_string(),
+        "max_tokens" => "length".to_string(),
+        "tool_use" => "tool_calls".to_string(),
+        "refusal" => "content_filter".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn convert_anthropic_response(response: AnthropicResponse) -> ChatCompletionResponse {
+    // Join the text blocks; anything else (tool use, thinking) isn't supported yet
+    let content = response.content.into_iter()
+        .filter_map(|block| match block {
+            AnthropicContentBlock::Text { text } => Some(text),
+            AnthropicContentBlock::Other => None,
+        })
+        .collect::<Vec<_>>()
+        .join("");
+
+    ChatCompletionResponse {
+        id: response.id,
+        choices: vec![ChatCompletionChoice {
+            message: ChatMessage {
+                role: MessageRole::Assistant,
+                content,
+                name: None,
+            },
+            finish_reason: response.stop_reason.as_deref().map(convert_stop_reason),
+            index: 0,
+        }],
+        created: chrono::Utc::now().timestamp() as u64,
+        model: response.model,
+        usage: Some(response.usage.into()),
+    }
+}
+
+fn convert_anthropic_model(model: AnthropicModelData) -> AIModel {
+    AIModel {
+        id: model.id.clone(),
+        name: model.display_name.clone().unwrap_or_else(|| model.id.clone()),
+        provider: "anthropic".to_string(),
+        capabilities: vec![ModelCapability::ChatCompletion],
+        context_length: None, // Filled in from the model catalog
+        max_output_tokens: None,
+        pricing: None,
+        deprecation_date: None,
+        additional_info: serde_json::to_value(model).unwrap_or_default(),
+    }
+}
+
+// What we remember between stream events to fill in each chunk
+struct AnthropicStreamState {
+    id: String,
+    created: u64,
+    input_tokens: u32,
+}
+
+/// Turn one streamed event into a chunk, or nothing if the event has nothing for us
+fn convert_anthropic_stream_event(
+    state: &mut AnthropicStreamState,
+    data: &str,
+) -> Option<Result<ChatCompletionChunk, AIProviderError>> {
+    let event: AnthropicStreamEvent = match serde_json::from_str(data) {
+        Ok(event) => event,
+        Err(e) => return Some(Err(AIProviderError::DeserializationError(
+            format!("Failed to parse stream event: {}: {}", e, data)
+        ))),
+    };
+
+    let chunk = |choice: ChatCompletionChunkChoice, usage: Option<TokenUsage>, state: &AnthropicStreamState| {
+        ChatCompletionChunk {
+            id: state.id.clone(),
+            choices: vec![choice],
+            created: state.created,
+            usage,
+        }
+    };
+
+    match event {
+        AnthropicStreamEvent::MessageStart { message } => {
+            state.id = message.id;
+            state.input_tokens = message.usage.input_tokens;
+            Some(Ok(chunk(ChatCompletionChunkChoice {
+                delta: ChatMessageDelta { role: Some(MessageRole::Assistant), content: None },
+                finish_reason: None,
+                index: 0,
+            }, None, state)))
+        },
+        AnthropicStreamEvent::ContentBlockDelta { delta: AnthropicDelta::TextDelta { text } } => {
+            Some(Ok(chunk(ChatCompletionChunkChoice {
+                delta: ChatMessageDelta { role: None, content: Some(text) },
+                finish_reason: None,
+                index: 0,
+            }, None, state)))
+        },
+        AnthropicStreamEvent::MessageDelta { delta, usage } => {
+            // Output tokens are cumulative here, input tokens were reported at message_start
+            let usage = AnthropicUsage {
+                input_tokens: state.input_tokens.max(usage.input_tokens),
+                output_tokens: usage.output_tokens,
+            };
+            Some(Ok(chunk(ChatCompletionChunkChoice {
+                delta: ChatMessageDelta { role: None, content: None },
+                finish_reason: delta.stop_reason.as_deref().map(convert_stop_reason),
+                index: 0,
+            }, Some(usage.into()), state)))
+        },
+        AnthropicStreamEvent::Error { error } => {
+            let mut details = ProviderErrorDetails::new(
+                classify(None, None, Some(&error.error_type), &error.message),
+                PROVIDER_NAME,
+                error.message,
+            );
+            details.error_type = Some(error.error_type);
+            Some(Err(AIProviderError::Provider(Box::new(details))))
+        },
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl ModelProvider for AnthropicProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        let url = format!("{}/models?limit=1000", self.base_url);
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.get(&url))).await?;
+        let response = check_response(PROVIDER_NAME, response).await?;
+
+        let model_list: AnthropicModelList = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(model_list.data.into_iter().map(convert_anthropic_model).collect())
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        let url = format!("{}/models/{}", self.base_url, model_id);
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.get(&url))).await?;
+        let response = check_response(PROVIDER_NAME, response).await?;
+
+        let model: AnthropicModelData = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(convert_anthropic_model(model))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        match self.get_model(&model_id).await {
+            Ok(model) => Ok(model),
+            Err(e) if e.kind() == AIErrorKind::ModelNotFound => {
+                // The list comes back newest first, so the first one is a sensible default
+                let models = self.list_models().await?;
+                models.into_iter().next()
+                    .ok_or_else(|| AIProviderError::ModelNotAvailable("No models available for Anthropic provider".to_string()))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "anthropic".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for AnthropicProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let body = AnthropicRequest::from_request(request, false)?;
+        let response = self.send_messages_request(&body).await?;
+
+        let anthropic_response: AnthropicResponse = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(convert_anthropic_response(anthropic_response))
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let body = AnthropicRequest::from_request(request, true)?;
+        let response = self.send_messages_request(&body).await?;
+
+        let state = AnthropicStreamState {
+            id: String::new(),
+            created: chrono::Utc::now().timestamp() as u64,
+            input_tokens: 0,
+        };
+
+        let stream = sse_data_stream(PROVIDER_NAME, response, self.connection.idle_timeout())
+            .scan(state, |state, data| {
+                let item = match data {
+                    Ok(data) => convert_anthropic_stream_event(state, &data),
+                    Err(e) => Some(Err(e)),
+                };
+                future::ready(Some(item))
+            })
+            .filter_map(future::ready);
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for AnthropicProvider {
+    async fn create_embeddings(
+        &self,
+        _embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        Err(AIProviderError::NotImplemented("Anthropic does not offer embedding models".to_string()))
+    }
+}
+
+impl PreferredEmbeddingModel for AnthropicProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        // Anthropic doesn't have an embedding model
+        String::new()
+    }
+}
+
+impl AIProvider for AnthropicProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::test_server::serve_once;
+    use serde_json::{json, Value};
+
+    /// A Server-Sent Events body carrying `events`, each named after its type like Anthropic's
+    fn sse_body(events: &[&str]) -> String {
+        events.iter()
+            .map(|data| {
+                let event: Value = serde_json::from_str(data).unwrap();
+                format!("event: {}\ndata: {}\n\n", event["type"].as_str().unwrap(), data)
+            })
+            .collect()
+    }
+
+    fn request(max_tokens: Option<u32>) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            messages: vec![
+                ChatMessage { role: MessageRole::System, content: "Be brief".to_string(), name: None },
+                ChatMessage { role: MessageRole::User, content: "Hello".to_string(), name: None },
+            ],
+            max_tokens,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn completion_defaults_max_tokens_and_moves_the_system_prompt() {
+        let (base_url, server) = serve_once("200 OK", "application/json", r#"{
+            "id": "msg_1",
+            "model": "claude-sonnet-4-5",
+            "content": [{"type": "text", "text": "Hi"}, {"type": "thinking", "thinking": "..."}, {"type": "text", "text": " there"}],
+            "stop_reason": "end_turn",
+            "usage": {"input_tokens": 9, "output_tokens": 2}
+        }"#).await;
+
+        let provider = AnthropicProvider::with_base_url("test-key", &base_url);
+        let response = provider.create_chat_completion(&request(None)).await.unwrap();
+        let sent = server.await.unwrap();
+
+        assert!(sent.head.starts_with("post /v1/messages "));
+        assert!(sent.head.contains("x-api-key: test-key"));
+        assert!(sent.head.contains("anthropic-version: 2023-06-01"));
+        assert_eq!(sent.body["max_tokens"], json!(DEFAULT_MAX_TOKENS));
+        assert_eq!(sent.body["system"], json!("Be brief"));
+        assert_eq!(sent.body["messages"], json!([{"role": "user", "content": "Hello"}]));
+        assert!(sent.body.get("stream").is_none());
+
+        assert_eq!(response.id, "msg_1");
+        assert_eq!(response.choices[0].message.content, "Hi there");
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!(response.usage.map(|usage| usage.total_tokens), Some(11));
+    }
+
+    #[tokio::test]
+    async fn stream_turns_events_into_chunks() {
+        let body = sse_body(&[
+            r#"{"type":"message_start","message":{"id":"msg_2","model":"claude-sonnet-4-5","usage":{"input_tokens":12,"output_tokens":1}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"ping"}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"lo"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens"},"usage":{"output_tokens":2}}"#,
+            r#"{"type":"message_stop"}"#,
+        ]);
+        let (base_url, server) = serve_once("200 OK", "text/event-stream", &body).await;
+
+        let provider = AnthropicProvider::with_base_url("test-key", &base_url);
+        let stream = provider.create_streaming_chat_completion(&request(Some(256))).await.unwrap();
+        let chunks: Vec<ChatCompletionChunk> = stream.map(Result::unwrap).collect().await;
+        let sent = server.await.unwrap();
+
+        assert_eq!(sent.body["stream"], json!(true));
+        assert_eq!(sent.body["max_tokens"], json!(256));
+
+        assert!(chunks.iter().all(|chunk| chunk.id == "msg_2"));
+        assert_eq!(chunks[0].choices[0].delta.role, Some(MessageRole::Assistant));
+        let text: Vec<String> = chunks.iter().filter_map(|chunk| chunk.choices[0].delta.content.clone()).collect();
+        assert_eq!(text, ["Hel", "lo"]);
+
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason.as_deref(), Some("length"));
+        let usage = last.usage.as_ref().unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.total_tokens), (12, 2, 14));
+    }
+
+    #[tokio::test]
+    async fn stream_error_event_ends_the_stream_with_a_classified_error() {
+        let body = sse_body(&[
+            r#"{"type":"message_start","message":{"id":"msg_3","model":"claude-sonnet-4-5","usage":{"input_tokens":5}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Par"}}"#,
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        ]);
+        let (base_url, server) = serve_once("200 OK", "text/event-stream", &body).await;
+
+        let provider = AnthropicProvider::with_base_url("test-key", &base_url);
+        let stream = provider.create_streaming_chat_completion(&request(None)).await.unwrap();
+        let items: Vec<Result<ChatCompletionChunk, AIProviderError>> = stream.collect().await;
+        server.await.unwrap();
+
+        assert_eq!(items.len(), 3);
+        let error = items.last().unwrap().as_ref().unwrap_err();
+        assert_eq!(error.kind(), AIErrorKind::Server);
+        assert_eq!(error.details().and_then(|details| details.error_type.as_deref()), Some("overloaded_error"));
+    }
+
+    #[tokio::test]
+    async fn error_responses_are_classified() {
+        let cases = [
+            (429, "Too Many Requests", "rate_limit_error", "Number of requests has exceeded your rate limit", AIErrorKind::RateLimited),
+            (401, "Unauthorized", "authentication_error", "invalid x-api-key", AIErrorKind::Auth),
+            (529, "Overloaded", "overloaded_error", "Overloaded", AIErrorKind::Server),
+            (400, "Bad Request", "invalid_request_error", "prompt is too long: 210000 tokens > 200000 maximum", AIErrorKind::ContextLengthExceeded),
+            (404, "Not Found", "not_found_error", "model: claude-nope", AIErrorKind::ModelNotFound),
+        ];
+
+        for (status, reason, error_type, message, kind) in cases {
+            let body = json!({"type": "error", "error": {"type": error_type, "message": message}}).to_string();
+            let (base_url, server) = serve_once(&format!("{} {}", status, reason), "application/json", &body).await;
+
+            let provider = AnthropicProvider::with_base_url("test-key", &base_url);
+            let error = provider.create_chat_completion(&request(None)).await.unwrap_err();
+            server.await.unwrap();
+
+            assert_eq!(error.kind(), kind, "{} {}", status, error_type);
+            let details = error.details().unwrap();
+            assert_eq!(details.status, Some(status));
+            assert_eq!(details.error_type.as_deref(), Some(error_type));
+            assert_eq!(details.message, message);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::ai::{
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, ChatCompletionStream, HealthStatus},
+    models::*,
+    resolver::can_chat,
+};
+use super::openai_compatible_provider::{OpenAICompatibleProvider, OpenAICompatibleConfig};
+use super::connection::ConnectionSettings;
+use super::retry::RetryPolicy;
+use async_trait::async_trait;
+use std::time::Instant;
+
+/// Azure OpenAI API version used when the configuration doesn't name one
+pub const AZURE_OPENAI_DEFAULT_API_VERSION: &str = "2024-10-21";
+
+/// Name used in errors
+const PROVIDER_NAME: &str = "Azure OpenAI";
+
+/// Settings for an Azure OpenAI resource
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AzureOpenAIConfig {
+    /// Resource endpoint, e.g. `https://my-resource.openai.azure.com`
+    pub endpoint: String,
+    /// Sent as the `api-key` header
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub api_version: Option<String>,
+    /// Model name as used in `ChatCompletionRequest.model` -> deployment name
+    #[serde(default)]
+    pub deployments: HashMap<String, String>,
+    /// Model name (a key of `deployments`) to use for embeddings
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+}
+
+/// Azure OpenAI implementation of the AI provider traits.
+///
+/// Azure addresses deployments rather than models, so each request's model name is routed
+/// through the configured deployment table. Names missing from the table are used as the
+/// deployment name directly. Deployments speak the OpenAI wire format, so requests go over the
+/// same HTTP path as other OpenAI compatible servers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureOpenAIProvider {
+    /// Bound to the resource's `/openai` routes; requests rebase it onto one deployment
+    client: OpenAICompatibleProvider,
+    endpoint: String,
+    deployments: HashMap<String, String>,
+    embedding_model: Option<String>,
+    preferred_model_name: Option<String>,
+}
+
+impl AzureOpenAIProvider {
+    /// Create a new Azure OpenAI provider for the given resource
+    pub fn new(config: &AzureOpenAIConfig) -> Self {
+        let endpoint = config.endpoint.trim_end_matches('/').to_string();
+        let api_version = config.api_version.clone()
+            .filter(|version| !version.is_empty())
+            .unwrap_or_else(|| AZURE_OPENAI_DEFAULT_API_VERSION.to_string());
+
+        let client_config = OpenAICompatibleConfig {
+            name: PROVIDER_NAME.to_string(),
+            base_url: format!("{}/openai", endpoint),
+            headers: HashMap::from([("api-key".to_string(), config.api_key.clone())]),
+            query: HashMap::from([("api-version".to_string(), api_version)]),
+            ..Default::default()
+        };
+
+        AzureOpenAIProvider {
+            client: OpenAICompatibleProvider::new(&client_config).with_provider_id("azure_openai"),
+            endpoint,
+            deployments: config.deployments.clone(),
+            embedding_model: config.embedding_model.clone(),
+            preferred_model_name: None,
+        }
+    }
+
+    /// Replace how 429s, 5xx responses and connection failures are retried
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.client.set_retry_policy(retry_policy);
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.client.set_connection_settings(connection)
+    }
+
+    /// The deployment that serves `model`
+    pub fn deployment_for(&self, model: &str) -> String {
+        self.deployments.get(model)
+            .cloned()
+            .unwrap_or_else(|| model.to_string())
+    }
+
+    /// The client rebased onto the deployment that serves `model`
+    fn client_for(&self, model: &str) -> OpenAICompatibleProvider {
+        let deployment = self.deployment_for(model);
+        self.client.with_base_url(&format!("{}/openai/deployments/{}", self.endpoint, deployment))
+    }
+
+    fn deployment_model(&self, model: &str, deployment: &str) -> AIModel {
+        AIModel {
+            id: model.to_string(),
+            name: model.to_string(),
+            provider: "azure_openai".to_string(),
+            capabilities: Vec::new(), // Filled in from the model catalog
+            context_length: None,
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: serde_json::json!({
+                "deployment": deployment,
+                "endpoint": self.endpoint,
+            }),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for AzureOpenAIProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        // Azure's data plane no longer lists deployments, so the configured table is the source of truth
+        let mut models: Vec<AIModel> = self.deployments.iter()
+            .map(|(model, deployment)| self.deployment_model(model, deployment))
+            .collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(models)
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        self.deployments.get(model_id)
+            .map(|deployment| self.deployment_model(model_id, deployment))
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(model_id.to_string()))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        if let Ok(model) = self.get_model(&model_id).await {
+            return Ok(model);
+        }
+
+        // Otherwise any deployment that can chat. The catalog only fills in capabilities once
+        // the models leave this provider, so unknown ones count.
+        let models = self.list_models().await?;
+        models.into_iter()
+            .find(can_chat)
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(
+                "No chat deployments configured for Azure OpenAI provider".to_string()
+            ))
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "azure_openai".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+
+    async fn health_check(&self) -> HealthStatus {
+        // The deployment table is local, so ask the resource for its models to prove the
+        // endpoint and key work
+        let started = Instant::now();
+        let reachable = match self.client.for_probe() {
+            Ok(probe) => probe.list_models().await,
+            Err(e) => Err(e),
+        };
+        match reachable {
+            Ok(_) => HealthStatus::from_models(self.get_provider_name(), started.elapsed(), self.list_models().await),
+            Err(e) => HealthStatus::failed(self.get_provider_name(), started.elapsed(), e),
+        }
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for AzureOpenAIProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        self.client_for(&request.model).create_chat_completion(request).await
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.client_for(&request.model).create_streaming_chat_completion(request).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for AzureOpenAIProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        self.client_for(&embedding_request.model).create_embeddings(embedding_request).await
+    }
+}
+
+impl PreferredEmbeddingModel for AzureOpenAIProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        self.embedding_model.clone()
+            .unwrap_or_else(|| "text-embedding-3-small".to_string())
+    }
+}
+
+impl AIProvider for AzureOpenAIProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::test_server::serve_once;
+    use crate::ai::traits::AIErrorKind;
+
+    fn provider(endpoint: &str) -> AzureOpenAIProvider {
+        AzureOpenAIProvider::new(&AzureOpenAIConfig {
+            endpoint: endpoint.to_string(),
+            api_key: "azure-key".to_string(),
+            deployments: HashMap::from([("gpt-4o".to_string(), "chat-deployment".to_string())]),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn health_check_asks_the_resource_for_its_models() {
+        let (base_url, server) = serve_once("200 OK", "application/json", r#"{"object":"list","data":[{"id":"gpt-4o","object":"model"}]}"#).await;
+        let endpoint = base_url.trim_end_matches("/v1");
+
+        let health = provider(endpoint).health_check().await;
+        let request = server.await.unwrap();
+
+        assert!(request.head.starts_with("get /openai/models?api-version=2024-10-21 "), "{}", request.head);
+        assert!(request.head.contains("api-key: azure-key"));
+        assert!(health.reachable);
+        assert_eq!(health.error, None);
+        // The configured deployments, not whatever the resource lists
+        assert_eq!(health.models, ["gpt-4o"]);
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_a_rejected_key() {
+        let (base_url, server) = serve_once(
+            "401 Unauthorized",
+            "application/json",
+            r#"{"error":{"code":"401","message":"Access denied due to invalid subscription key."}}"#,
+        ).await;
+
+        let health = provider(base_url.trim_end_matches("/v1")).health_check().await;
+        server.await.unwrap();
+
+        assert!(health.reachable);
+        assert_eq!(health.error_kind, Some(AIErrorKind::Auth));
+        assert!(health.models.is_empty());
+    }
+
+    #[tokio::test]
+    async fn health_check_fails_when_nothing_listens() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let health = provider(&endpoint).health_check().await;
+
+        assert!(!health.reachable);
+        assert_eq!(health.error_kind, Some(AIErrorKind::Network));
+    }
+
+    #[tokio::test]
+    async fn chat_requests_go_to_the_model_deployment() {
+        let (base_url, server) = serve_once(
+            "200 OK",
+            "application/json",
+            r#"{"id":"1","object":"chat.completion","created":0,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"Hi"},"finish_reason":"stop"}]}"#,
+        ).await;
+        let request = ChatCompletionRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage { role: MessageRole::User, content: "Hello".to_string(), name: None }],
+            ..Default::default()
+        };
+
+        let response = provider(base_url.trim_end_matches("/v1")).create_chat_completion(&request).await.unwrap();
+        let captured = server.await.unwrap();
+
+        assert!(captured.head.starts_with("post /openai/deployments/chat-deployment/chat/completions?api-version=2024-10-21 "), "{}", captured.head);
+        assert_eq!(response.choices[0].message.content, "Hi");
+    }
+}
@@ -0,0 +1,475 @@
+use crate::ai::traits::{ChatCompletionProvider, DiffusingProvider, ModelProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, ChatCompletionStream, HealthStatus};
+use crate::ai::models::*;
+use super::Provider;
+use super::mock_provider::{MockError, fnv1a};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// How a `CassetteProvider` treats the wrapped provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CassetteMode {
+    /// Call the provider and save every exchange, replacing earlier recordings of the same request
+    Record,
+    /// Answer only from the cassette; unrecorded requests fail
+    Replay,
+    /// Replay recorded requests and record the rest
+    ReplayOrRecord,
+}
+
+impl CassetteMode {
+    /// Parse "record", "replay" or "auto"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "record" => Some(CassetteMode::Record),
+            "replay" => Some(CassetteMode::Replay),
+            "auto" => Some(CassetteMode::ReplayOrRecord),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExchangeKind {
+    Chat,
+    Stream,
+    Diffusing,
+}
+
+/// The parts of a request that decide its answer, normalized so equivalent requests hash alike
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CassetteRequest {
+    kind: ExchangeKind,
+    model: String,
+    messages: Vec<ChatMessage>,
+    // Sampling params are kept as fixed precision text so float noise doesn't change the key
+    temperature: Option<String>,
+    top_p: Option<String>,
+    max_tokens: Option<u32>,
+    stop: Option<Vec<String>>,
+    seed: Option<i64>,
+}
+
+impl CassetteRequest {
+    fn new(kind: ExchangeKind, request: &ChatCompletionRequest) -> Self {
+        CassetteRequest {
+            kind,
+            model: request.model.clone(),
+            messages: request.messages.clone(),
+            temperature: request.temperature.map(|t| format!("{:.3}", t)),
+            top_p: request.top_p.map(|p| format!("{:.3}", p)),
+            max_tokens: request.max_tokens,
+            stop: request.stop.clone().map(|mut stop| {
+                stop.sort();
+                stop
+            }),
+            seed: request.seed,
+        }
+    }
+
+    fn key(&self) -> String {
+        let canonical = serde_json::to_string(self).unwrap_or_default();
+        format!("{:016x}", fnv1a(canonical.as_bytes()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CassetteResponse {
+    Completion(ChatCompletionResponse),
+    /// A stream's chunks, and the error that ended it early if any
+    Chunks {
+        chunks: Vec<ChatCompletionChunk>,
+        error: Option<MockError>,
+    },
+    Error(MockError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CassetteInteraction {
+    key: String,
+    request: CassetteRequest,
+    response: CassetteResponse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<CassetteInteraction>,
+}
+
+/// Records a provider's chat and streaming exchanges to a JSON cassette file and replays them.
+///
+/// Exchanges are keyed by a hash of the normalized request (model, messages and sampling
+/// params). Model listing and embeddings always go to the wrapped provider.
+#[derive(Debug, Clone, Serialize)]
+pub struct CassetteProvider {
+    inner: Box<Provider>,
+    path: PathBuf,
+    mode: CassetteMode,
+    #[serde(skip)]
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl CassetteProvider {
+    /// Wrap `inner`, loading the cassette at `path` if it exists
+    pub fn new(inner: Provider, path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self, AIProviderError> {
+        let path = path.as_ref().to_path_buf();
+
+        let cassette = if path.exists() {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| AIProviderError::Other(format!("Failed to read cassette {}: {}", path.display(), e)))?;
+            serde_json::from_str(&contents)
+                .map_err(|e| AIProviderError::DeserializationError(format!("Invalid cassette {}: {}", path.display(), e)))?
+        } else if mode == CassetteMode::Replay {
+            return Err(AIProviderError::Other(format!("Cassette {} does not exist", path.display())));
+        } else {
+            Cassette::default()
+        };
+
+        Ok(CassetteProvider {
+            inner: Box::new(inner),
+            path,
+            mode,
+            cassette: Arc::new(Mutex::new(cassette)),
+        })
+    }
+
+    /// The recorded answer for `request`, unless this exchange should go to the provider
+    fn replay(&self, request: &CassetteRequest) -> Result<Option<CassetteResponse>, AIProviderError> {
+        if self.mode == CassetteMode::Record {
+            return Ok(None);
+        }
+
+        let key = request.key();
+        let recorded = self.cassette.lock().unwrap().interactions.iter()
+            .find(|interaction| interaction.key == key)
+            .map(|interaction| interaction.response.clone());
+
+        match recorded {
+            Some(response) => Ok(Some(response)),
+            None if self.mode == CassetteMode::Replay => Err(AIProviderError::Other(format!(
+                "No recorded {:?} exchange for request {} in cassette {}", request.kind, key, self.path.display()
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn record(&self, request: CassetteRequest, response: CassetteResponse) -> Result<(), AIProviderError> {
+        record_into(&self.cassette, &self.path, request, response)
+    }
+
+    async fn stream(
+        &self,
+        request: &ChatCompletionRequest,
+        kind: ExchangeKind,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let cassette_request = CassetteRequest::new(kind, request);
+
+        match self.replay(&cassette_request)? {
+            Some(CassetteResponse::Chunks { chunks, error }) => {
+                let items: Vec<Result<ChatCompletionChunk, AIProviderError>> = chunks.into_iter()
+                    .map(Ok)
+                    .chain(error.map(|e| Err(e.into())))
+                    .collect();
+                return Ok(Box::pin(futures::stream::iter(items)));
+            },
+            Some(CassetteResponse::Error(error)) => return Err(error.into()),
+            Some(CassetteResponse::Completion(_)) => {
+                return Err(AIProviderError::DeserializationError(
+                    "Cassette holds a completion where a stream was expected".to_string()
+                ));
+            },
+            None => {},
+        }
+
+        let opened = match kind {
+            ExchangeKind::Diffusing => self.inner.create_diffusing_stream(request).await,
+            _ => self.inner.create_streaming_chat_completion(request).await,
+        };
+        let inner_stream = match opened {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.record(cassette_request, CassetteResponse::Error((&e).into()))?;
+                return Err(e);
+            },
+        };
+
+        // Pass chunks through as they arrive and save the exchange once the stream ends
+        let cassette = self.cassette.clone();
+        let path = self.path.clone();
+        let state = (inner_stream, Vec::new(), Some(cassette_request));
+        let recording = futures::stream::unfold(state, move |(mut inner_stream, mut chunks, mut pending)| {
+            let cassette = cassette.clone();
+            let path = path.clone();
+            async move {
+                let cassette_request = pending.take()?;
+                match inner_stream.next().await {
+                    Some(Ok(chunk)) => {
+                        chunks.push(chunk.clone());
+                        Some((Ok(chunk), (inner_stream, chunks, Some(cassette_request))))
+                    },
+                    Some(Err(e)) => {
+                        let response = CassetteResponse::Chunks { chunks: chunks.clone(), error: Some((&e).into()) };
+                        if let Err(record_error) = record_into(&cassette, &path, cassette_request, response) {
+                            log::warn!("{}", record_error);
+                        }
+                        Some((Err(e), (inner_stream, chunks, None)))
+                    },
+                    None => {
+                        let response = CassetteResponse::Chunks { chunks: chunks.clone(), error: None };
+                        if let Err(record_error) = record_into(&cassette, &path, cassette_request, response) {
+                            log::warn!("{}", record_error);
+                        }
+                        None
+                    },
+                }
+            }
+        });
+
+        Ok(Box::pin(recording))
+    }
+}
+
+/// Add or replace an interaction and rewrite the cassette file
+fn record_into(
+    cassette: &Mutex<Cassette>,
+    path: &Path,
+    request: CassetteRequest,
+    response: CassetteResponse,
+) -> Result<(), AIProviderError> {
+    let mut cassette = cassette.lock().unwrap();
+    let key = request.key();
+    cassette.interactions.retain(|interaction| interaction.key != key);
+    cassette.interactions.push(CassetteInteraction { key, request, response });
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AIProviderError::Other(format!("Failed to create cassette directory: {}", e)))?;
+    }
+    let contents = serde_json::to_string_pretty(&*cassette)
+        .map_err(|e| AIProviderError::Other(format!("Failed to serialize cassette: {}", e)))?;
+    std::fs::write(path, contents)
+        .map_err(|e| AIProviderError::Other(format!("Failed to write cassette {}: {}", path.display(), e)))
+}
+
+#[async_trait]
+impl ModelProvider for CassetteProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        self.inner.list_models().await
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        self.inner.get_model(model_id).await
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        self.inner.get_preferred_inference_model(preference_model).await
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.inner.set_preferred_inference_model(model_name)
+    }
+
+    fn get_provider_name(&self) -> String {
+        self.inner.get_provider_name()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.inner.get_preferred_model_name()
+    }
+
+    async fn health_check(&self) -> HealthStatus {
+        self.inner.health_check().await
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for CassetteProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let cassette_request = CassetteRequest::new(ExchangeKind::Chat, request);
+
+        match self.replay(&cassette_request)? {
+            Some(CassetteResponse::Completion(response)) => return Ok(response),
+            Some(CassetteResponse::Error(error)) => return Err(error.into()),
+            Some(CassetteResponse::Chunks { .. }) => {
+                return Err(AIProviderError::DeserializationError(
+                    "Cassette holds a stream where a completion was expected".to_string()
+                ));
+            },
+            None => {},
+        }
+
+        match self.inner.create_chat_completion(request).await {
+            Ok(response) => {
+                self.record(cassette_request, CassetteResponse::Completion(response.clone()))?;
+                Ok(response)
+            },
+            Err(e) => {
+                self.record(cassette_request, CassetteResponse::Error((&e).into()))?;
+                Err(e)
+            },
+        }
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.stream(request, ExchangeKind::Stream).await
+    }
+}
+
+#[async_trait]
+impl DiffusingProvider for CassetteProvider {
+    async fn create_diffusing_stream(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.stream(request, ExchangeKind::Diffusing).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for CassetteProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        self.inner.create_embeddings(embedding_request).await
+    }
+}
+
+impl PreferredEmbeddingModel for CassetteProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        self.inner.get_preferred_embedding_model()
+    }
+}
+
+impl AIProvider for CassetteProvider {
+    fn as_diffusing(&self) -> Option<&dyn DiffusingProvider> {
+        // Only when the wrapped provider could answer a diffusing request
+        self.inner.as_diffusing().map(|_| self as &dyn DiffusingProvider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::mock_provider::{test_support::{self, mock}, MOCK_DEFAULT_MODEL};
+
+    const FIXTURES: &str = r#"
+default_response: "A recorded answer, streamed in pieces."
+chunk_size: 10
+responses:
+  - prompt: "a broken stream"
+    response: "This answer stops partway through."
+    error: {kind: APIError, message: Connection reset}
+    fail_after_chunks: 2
+"#;
+
+    /// A cassette path of its own for each test, removed when dropped
+    struct TempCassette(PathBuf);
+
+    impl TempCassette {
+        fn new() -> Self {
+            TempCassette(std::env::temp_dir().join(format!("cassette-{}.json", uuid::Uuid::new_v4())))
+        }
+    }
+
+    impl Drop for TempCassette {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// With a temperature, so replay can be checked to tolerate float noise in it
+    fn request(prompt: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest { temperature: Some(0.7), ..test_support::request(MOCK_DEFAULT_MODEL, prompt) }
+    }
+
+    async fn drain(stream: ChatCompletionStream) -> (serde_json::Value, Option<AIProviderError>) {
+        let items: Vec<Result<ChatCompletionChunk, AIProviderError>> = stream.collect().await;
+        let mut chunks = Vec::new();
+        let mut error = None;
+        for item in items {
+            match item {
+                Ok(chunk) => chunks.push(chunk),
+                Err(e) => error = Some(e),
+            }
+        }
+        (serde_json::to_value(chunks).unwrap(), error)
+    }
+
+    #[tokio::test]
+    async fn completion_replays_offline() {
+        let cassette = TempCassette::new();
+
+        let recorder = CassetteProvider::new(mock(FIXTURES), &cassette.0, CassetteMode::Record).unwrap();
+        let recorded = recorder.create_chat_completion(&request("Hello")).await.unwrap();
+        assert!(cassette.0.exists());
+
+        // Anything that reached the wrapped provider now would come back with a different answer
+        let player = CassetteProvider::new(mock("default_response: Live answer"), &cassette.0, CassetteMode::Replay).unwrap();
+        let replayed = player.create_chat_completion(&request("Hello")).await.unwrap();
+        assert_eq!(serde_json::to_value(&replayed).unwrap(), serde_json::to_value(&recorded).unwrap());
+        assert_eq!(replayed.choices[0].message.content, "A recorded answer, streamed in pieces.");
+
+        // Float noise in sampling params still finds the recording
+        let noisy = ChatCompletionRequest { temperature: Some(0.7000001), ..request("Hello") };
+        assert!(player.create_chat_completion(&noisy).await.is_ok());
+
+        let unrecorded = player.create_chat_completion(&request("Something else")).await.unwrap_err();
+        assert!(unrecorded.to_string().contains("No recorded Chat exchange"));
+        let wrong_kind = player.create_streaming_chat_completion(&request("Hello")).await.err().unwrap();
+        assert!(wrong_kind.to_string().contains("No recorded Stream exchange"));
+    }
+
+    #[tokio::test]
+    async fn stream_replays_offline() {
+        let cassette = TempCassette::new();
+
+        let recorder = CassetteProvider::new(mock(FIXTURES), &cassette.0, CassetteMode::Record).unwrap();
+        let (recorded, error) = drain(recorder.create_streaming_chat_completion(&request("Hello")).await.unwrap()).await;
+        assert!(error.is_none());
+        let (recorded_broken, broken_error) = drain(recorder.create_streaming_chat_completion(&request("a broken stream")).await.unwrap()).await;
+        assert_eq!(broken_error.map(|e| e.to_string()).as_deref(), Some("API error: Connection reset"));
+
+        let player = CassetteProvider::new(mock("default_response: Live answer"), &cassette.0, CassetteMode::Replay).unwrap();
+        let (replayed, error) = drain(player.create_streaming_chat_completion(&request("Hello")).await.unwrap()).await;
+        assert!(error.is_none());
+        assert_eq!(replayed, recorded);
+        assert_eq!(replayed.as_array().unwrap().len(), 4);
+
+        // A stream that failed partway replays its chunks, then the same error
+        let (replayed_broken, broken_error) = drain(player.create_streaming_chat_completion(&request("a broken stream")).await.unwrap()).await;
+        assert_eq!(replayed_broken, recorded_broken);
+        assert_eq!(replayed_broken.as_array().unwrap().len(), 2);
+        assert_eq!(broken_error.map(|e| e.to_string()).as_deref(), Some("API error: Connection reset"));
+    }
+
+    #[tokio::test]
+    async fn replay_or_record_only_calls_the_provider_once() {
+        let cassette = TempCassette::new();
+        // The fixture answers once; a second call to the provider would get the fallback text
+        let provider = mock("{default_response: Second call, responses: [{prompt: Hello, response: First call, times: 1}]}");
+
+        let auto = CassetteProvider::new(provider, &cassette.0, CassetteMode::ReplayOrRecord).unwrap();
+        let first = auto.create_chat_completion(&request("Hello")).await.unwrap();
+        let second = auto.create_chat_completion(&request("Hello")).await.unwrap();
+
+        assert_eq!(first.choices[0].message.content, "First call");
+        assert_eq!(second.choices[0].message.content, "First call");
+    }
+
+    #[test]
+    fn replay_needs_an_existing_cassette() {
+        let cassette = TempCassette::new();
+        assert!(CassetteProvider::new(mock(FIXTURES), &cassette.0, CassetteMode::Replay).is_err());
+    }
+}
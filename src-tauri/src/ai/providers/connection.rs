@@ -0,0 +1,209 @@
+use crate::ai::traits::{AIErrorKind, AIProviderError};
+use super::http_error::network_error;
+use futures::{Stream, StreamExt};
+use reqwest::header::HeaderMap;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Sent as the User-Agent unless the settings name another
+pub const DEFAULT_USER_AGENT: &str = concat!("sidehustler/", env!("CARGO_PKG_VERSION"));
+
+const DEFAULT_CONNECT_TIMEOUT_MS: u64 = 10_000;
+/// Local servers may load a model before answering, so the first byte gets a while
+const DEFAULT_FIRST_BYTE_TIMEOUT_MS: u64 = 120_000;
+const DEFAULT_IDLE_TIMEOUT_MS: u64 = 60_000;
+const DEFAULT_TOTAL_TIMEOUT_MS: u64 = 600_000;
+/// Longest a health probe waits, so a server that is down or stuck shows up in seconds
+const PROBE_CONNECT_TIMEOUT_MS: u64 = 3_000;
+const PROBE_TIMEOUT_MS: u64 = 5_000;
+
+/// Timeouts and network settings for a provider's HTTP clients. Anything left unset uses the
+/// defaults; a timeout of 0 turns that timeout off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionSettings {
+    /// Time allowed to open the connection
+    pub connect_timeout_ms: Option<u64>,
+    /// Time allowed from sending a request to the response headers
+    pub first_byte_timeout_ms: Option<u64>,
+    /// Longest silence allowed between chunks of a streamed response
+    pub idle_timeout_ms: Option<u64>,
+    /// Time allowed for the whole request, streamed body included
+    pub total_timeout_ms: Option<u64>,
+    /// Proxy for all requests, e.g. "http://proxy.internal:3128" or "socks5://127.0.0.1:1080"
+    pub proxy: Option<String>,
+    /// PEM file of extra root certificates, for gateways behind a corporate CA
+    pub ca_bundle: Option<PathBuf>,
+    pub user_agent: Option<String>,
+}
+
+/// The saved connection settings: defaults for every provider, and overrides keyed by provider
+/// id, or by `provider_id:endpoint` for a single OpenAI compatible endpoint
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    pub defaults: ConnectionSettings,
+    pub providers: HashMap<String, ConnectionSettings>,
+}
+
+impl ConnectionConfig {
+    /// The defaults, overridden by the provider's settings and then by the endpoint's
+    pub fn settings_for(&self, provider_id: &str, endpoint: Option<&str>) -> ConnectionSettings {
+        let mut settings = self.defaults.clone();
+        if let Some(overrides) = self.providers.get(provider_id) {
+            settings = settings.merged_with(overrides);
+        }
+        if let Some(overrides) = endpoint.and_then(|endpoint| self.providers.get(&format!("{}:{}", provider_id, endpoint))) {
+            settings = settings.merged_with(overrides);
+        }
+        settings
+    }
+}
+
+/// A timeout of at most `limit_ms`; one that is off or longer is cut down to the limit
+fn capped(value: Option<u64>, default_ms: u64, limit_ms: u64) -> Option<u64> {
+    match value.unwrap_or(default_ms) {
+        0 => Some(limit_ms),
+        ms => Some(ms.min(limit_ms)),
+    }
+}
+
+fn timeout(value: Option<u64>, default_ms: u64) -> Option<Duration> {
+    match value.unwrap_or(default_ms) {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+impl ConnectionSettings {
+    /// Layer `overrides` on top of these settings; any field set there wins
+    pub fn merged_with(&self, overrides: &ConnectionSettings) -> Self {
+        let overrides = overrides.clone();
+        ConnectionSettings {
+            connect_timeout_ms: overrides.connect_timeout_ms.or(self.connect_timeout_ms),
+            first_byte_timeout_ms: overrides.first_byte_timeout_ms.or(self.first_byte_timeout_ms),
+            idle_timeout_ms: overrides.idle_timeout_ms.or(self.idle_timeout_ms),
+            total_timeout_ms: overrides.total_timeout_ms.or(self.total_timeout_ms),
+            proxy: overrides.proxy.or_else(|| self.proxy.clone()),
+            ca_bundle: overrides.ca_bundle.or_else(|| self.ca_bundle.clone()),
+            user_agent: overrides.user_agent.or_else(|| self.user_agent.clone()),
+        }
+    }
+
+    /// These settings for a health probe: the same proxy and certificates, with timeouts cut
+    /// down to a few seconds
+    pub fn for_probe(&self) -> ConnectionSettings {
+        ConnectionSettings {
+            connect_timeout_ms: capped(self.connect_timeout_ms, DEFAULT_CONNECT_TIMEOUT_MS, PROBE_CONNECT_TIMEOUT_MS),
+            first_byte_timeout_ms: capped(self.first_byte_timeout_ms, DEFAULT_FIRST_BYTE_TIMEOUT_MS, PROBE_TIMEOUT_MS),
+            total_timeout_ms: capped(self.total_timeout_ms, DEFAULT_TOTAL_TIMEOUT_MS, PROBE_TIMEOUT_MS),
+            ..self.clone()
+        }
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        timeout(self.connect_timeout_ms, DEFAULT_CONNECT_TIMEOUT_MS)
+    }
+
+    pub fn first_byte_timeout(&self) -> Option<Duration> {
+        timeout(self.first_byte_timeout_ms, DEFAULT_FIRST_BYTE_TIMEOUT_MS)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        timeout(self.idle_timeout_ms, DEFAULT_IDLE_TIMEOUT_MS)
+    }
+
+    pub fn total_timeout(&self) -> Option<Duration> {
+        timeout(self.total_timeout_ms, DEFAULT_TOTAL_TIMEOUT_MS)
+    }
+
+    /// A client with these settings and `default_headers` on every request. First-byte and idle
+    /// timeouts can't be expressed on the client; `send` and `with_idle_timeout` apply those.
+    pub fn build_client(&self, default_headers: HeaderMap) -> Result<reqwest::Client, AIProviderError> {
+        let mut builder = reqwest::Client::builder()
+            .default_headers(default_headers)
+            .user_agent(self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT));
+
+        if let Some(connect_timeout) = self.connect_timeout() {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(total_timeout) = self.total_timeout() {
+            builder = builder.timeout(total_timeout);
+        }
+        if let Some(proxy) = self.proxy.as_deref().filter(|proxy| !proxy.is_empty()) {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| AIProviderError::InvalidRequest(format!("Invalid proxy {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ca_bundle) = &self.ca_bundle {
+            let pem = std::fs::read(ca_bundle)
+                .map_err(|e| AIProviderError::InvalidRequest(format!("Failed to read CA bundle {}: {}", ca_bundle.display(), e)))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| AIProviderError::InvalidRequest(format!("Invalid CA bundle {}: {}", ca_bundle.display(), e)))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        builder.build()
+            .map_err(|e| AIProviderError::Other(format!("Failed to create HTTP client: {}", e)))
+    }
+
+    /// The error for a request that got no response headers in time
+    pub(crate) fn first_byte_timeout_error(&self, provider: &str) -> AIProviderError {
+        let waited = self.first_byte_timeout().unwrap_or_default();
+        AIProviderError::provider(AIErrorKind::Timeout, provider, format!("No response within {} ms", waited.as_millis()))
+    }
+
+    /// Send `request`, giving up when the response headers take longer than the first-byte timeout
+    pub(crate) async fn send(&self, provider: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response, AIProviderError> {
+        self.first_byte(provider, request.send()).await?
+            .map_err(|e| network_error(provider, &e))
+    }
+
+    /// Wait for `future` no longer than the first-byte timeout
+    pub(crate) async fn first_byte<T>(&self, provider: &str, future: impl std::future::Future<Output = T>) -> Result<T, AIProviderError> {
+        match self.first_byte_timeout() {
+            Some(limit) => tokio::time::timeout(limit, future).await
+                .map_err(|_| self.first_byte_timeout_error(provider)),
+            None => Ok(future.await),
+        }
+    }
+
+    /// End `stream` with a timeout error when it goes quiet for longer than the idle timeout
+    pub(crate) fn with_idle_timeout<T, S>(&self, provider: &str, stream: S) -> impl Stream<Item = Result<T, AIProviderError>> + Send
+    where
+        T: Send,
+        S: Stream<Item = Result<T, AIProviderError>> + Send + Unpin,
+    {
+        idle_timeout_stream(provider, self.idle_timeout(), stream)
+    }
+}
+
+/// End `stream` with a timeout error when nothing arrives for `idle_timeout`
+pub(crate) fn idle_timeout_stream<T, S>(provider: &str, idle_timeout: Option<Duration>, stream: S) -> impl Stream<Item = Result<T, AIProviderError>> + Send
+where
+    T: Send,
+    S: Stream<Item = Result<T, AIProviderError>> + Send + Unpin,
+{
+    let provider = provider.to_string();
+    futures::stream::unfold(Some(stream), move |state| {
+        let provider = provider.clone();
+        async move {
+            let mut stream = state?;
+            let next = match idle_timeout {
+                Some(idle) => match tokio::time::timeout(idle, stream.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        let message = format!("Stream stalled: nothing received for {} ms", idle.as_millis());
+                        return Some((Err(AIProviderError::provider(AIErrorKind::Timeout, &provider, message)), None));
+                    },
+                },
+                None => stream.next().await,
+            };
+            next.map(|item| (item, Some(stream)))
+        }
+    })
+}
@@ -0,0 +1,166 @@
+use crate::ai::traits::{ModelProvider, HealthStatus};
+use super::{LMStudioProvider, OllamaProvider, OpenAICompatibleProvider, OpenAICompatibleConfig, RetryPolicy};
+use super::connection::ConnectionSettings;
+use reqwest::header::HeaderMap;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+/// A local inference server we know how to find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LocalServerKind {
+    LMStudio,
+    Ollama,
+    LlamaCpp,
+    VLLM,
+}
+
+impl LocalServerKind {
+    pub const ALL: [LocalServerKind; 4] = [
+        LocalServerKind::LMStudio,
+        LocalServerKind::Ollama,
+        LocalServerKind::LlamaCpp,
+        LocalServerKind::VLLM,
+    ];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            LocalServerKind::LMStudio => "LM Studio",
+            LocalServerKind::Ollama => "Ollama",
+            LocalServerKind::LlamaCpp => "llama.cpp",
+            LocalServerKind::VLLM => "vLLM",
+        }
+    }
+
+    /// The port the server listens on out of the box
+    pub fn default_port(self) -> u16 {
+        match self {
+            LocalServerKind::LMStudio => 1234,
+            LocalServerKind::Ollama => 11434,
+            LocalServerKind::LlamaCpp => 8080,
+            LocalServerKind::VLLM => 8000,
+        }
+    }
+
+    /// The registered provider id that talks to this server
+    pub fn provider_id(self) -> &'static str {
+        match self {
+            LocalServerKind::LMStudio => "LMStudio",
+            LocalServerKind::Ollama => "Ollama",
+            LocalServerKind::LlamaCpp | LocalServerKind::VLLM => "OpenAICompatible",
+        }
+    }
+
+    /// The base URL in the form that provider's settings expect
+    pub fn base_url(self, host: &str, port: u16) -> String {
+        match self {
+            LocalServerKind::LMStudio => format!("http://{}:{}/v1/", host, port),
+            LocalServerKind::Ollama => format!("http://{}:{}", host, port),
+            LocalServerKind::LlamaCpp | LocalServerKind::VLLM => format!("http://{}:{}/v1", host, port),
+        }
+    }
+
+    /// Where the server reports its version, and the field holding it. Ollama's provider asks
+    /// on its own; LM Studio doesn't say.
+    fn version_endpoint(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            LocalServerKind::LlamaCpp => Some(("props", "build_info")),
+            LocalServerKind::VLLM => Some(("version", "version")),
+            _ => None,
+        }
+    }
+}
+
+/// A local server probed on its well-known port
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredServer {
+    pub kind: LocalServerKind,
+    pub display_name: String,
+    /// Provider id to use it with, see `LocalServerKind::provider_id`
+    pub provider_id: String,
+    pub base_url: String,
+    pub health: HealthStatus,
+}
+
+/// Settings for probing localhost: never through the proxy, and with the probe timeouts, the
+/// connect one shorter still so a closed port fails fast
+fn probe_settings(connection: &ConnectionSettings) -> ConnectionSettings {
+    ConnectionSettings {
+        connect_timeout_ms: Some(500),
+        proxy: None,
+        ..connection.for_probe()
+    }
+}
+
+/// Probe every known server kind on its default localhost port, all at once
+pub async fn discover_local_servers(connection: &ConnectionSettings) -> Vec<DiscoveredServer> {
+    let probes = LocalServerKind::ALL.iter().map(|kind| async move {
+        let base_url = kind.base_url("localhost", kind.default_port());
+        probe_local_server(*kind, &base_url, connection).await
+    });
+    futures::future::join_all(probes).await
+}
+
+/// Check whether a server of `kind` answers at `base_url`, and what it serves
+pub async fn probe_local_server(kind: LocalServerKind, base_url: &str, connection: &ConnectionSettings) -> DiscoveredServer {
+    let settings = probe_settings(connection);
+    let mut health = match kind {
+        LocalServerKind::LMStudio => {
+            let mut provider = LMStudioProvider::new(base_url, None);
+            provider.set_retry_policy(RetryPolicy::none());
+            match provider.set_connection_settings(settings.clone()) {
+                Ok(()) => provider.health_check().await,
+                Err(e) => HealthStatus::failed(provider.get_provider_name(), Default::default(), e),
+            }
+        },
+        LocalServerKind::Ollama => {
+            let mut provider = OllamaProvider::new(base_url);
+            provider.set_retry_policy(RetryPolicy::none());
+            match provider.set_connection_settings(settings.clone()) {
+                Ok(()) => provider.health_check().await,
+                Err(e) => HealthStatus::failed(provider.get_provider_name(), Default::default(), e),
+            }
+        },
+        LocalServerKind::LlamaCpp | LocalServerKind::VLLM => {
+            let mut provider = OpenAICompatibleProvider::new(&OpenAICompatibleConfig {
+                name: kind.display_name().to_string(),
+                base_url: base_url.to_string(),
+                ..Default::default()
+            });
+            provider.set_retry_policy(RetryPolicy::none());
+            match provider.set_connection_settings(settings.clone()) {
+                Ok(()) => provider.health_check().await,
+                Err(e) => HealthStatus::failed(provider.get_provider_name(), Default::default(), e),
+            }
+        },
+    };
+
+    if health.reachable {
+        if let Some((path, field)) = kind.version_endpoint() {
+            health.version = server_version(base_url, path, field, &settings).await;
+        }
+        // These servers only serve the models they were started with
+        if matches!(kind, LocalServerKind::LlamaCpp | LocalServerKind::VLLM) && health.error.is_none() {
+            health.loaded_models = Some(health.models.clone());
+        }
+    }
+
+    DiscoveredServer {
+        kind,
+        display_name: kind.display_name().to_string(),
+        provider_id: kind.provider_id().to_string(),
+        base_url: base_url.to_string(),
+        health,
+    }
+}
+
+/// Read `field` from the JSON at `path` under the server's root; None when anything goes wrong
+async fn server_version(base_url: &str, path: &str, field: &str, settings: &ConnectionSettings) -> Option<String> {
+    let root = base_url.trim_end_matches('/').trim_end_matches("/v1");
+    let client = settings.build_client(HeaderMap::new()).ok()?;
+    let response = client.get(format!("{}/{}", root, path)).send().await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    let body: Value = response.json().await.ok()?;
+    body[field].as_str().map(str::to_string)
+}
@@ -0,0 +1,369 @@
+use crate::ai::traits::{ChatCompletionProvider, ModelProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, AIErrorKind, ChatCompletionStream};
+use crate::ai::models::*;
+use crate::ai::resolver::{ModelResolver, ResolvedModel};
+use super::Provider;
+use async_trait::async_trait;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::future::Future;
+
+/// A saved fallback chain step, before its provider is built
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FallbackLinkConfig {
+    /// Registered provider id, e.g. "LMStudio"
+    pub provider: String,
+    /// Saved endpoint name, for "OpenAICompatible"
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Model to use on this provider whatever was requested
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Requested model -> model to use on this provider
+    #[serde(default)]
+    pub model_map: HashMap<String, String>,
+}
+
+/// One step of a fallback chain
+#[derive(Debug, Clone)]
+pub struct FallbackLink {
+    pub provider: Provider,
+    pub model: Option<String>,
+    pub model_map: HashMap<String, String>,
+}
+
+impl FallbackLink {
+    /// A step that sends requests with the model they ask for
+    pub fn new(provider: Provider) -> Self {
+        FallbackLink {
+            provider,
+            model: None,
+            model_map: HashMap::new(),
+        }
+    }
+
+    /// The model to ask this step's provider for
+    pub fn model_for(&self, requested: &str) -> String {
+        self.model_map.get(requested)
+            .or(self.model.as_ref())
+            .cloned()
+            .unwrap_or_else(|| requested.to_string())
+    }
+
+    /// The model this step must use, if the request or the step names one
+    fn explicit_model(&self, requested: &str) -> Option<String> {
+        Some(self.model_for(requested)).filter(|model| !model.is_empty())
+    }
+}
+
+/// A provider that failed before the one that answered
+#[derive(Debug, Clone, Serialize)]
+pub struct FallbackAttempt {
+    pub provider: String,
+    pub model: String,
+    pub error: String,
+}
+
+/// A completion and who produced it
+#[derive(Debug, Clone)]
+pub struct FallbackResponse {
+    pub response: ChatCompletionResponse,
+    /// Name of the provider that answered
+    pub provider: String,
+    /// Model requested from that provider
+    pub model: String,
+    /// That model as the provider describes it, and how it was picked
+    pub resolved: ResolvedModel,
+    pub attempts: Vec<FallbackAttempt>,
+}
+
+/// Tries an ordered chain of providers, moving on to the next one when a provider fails with a
+/// retryable error. Streams fall back only while opening; once chunks flow the provider is fixed.
+#[derive(Debug, Clone, Default)]
+pub struct FallbackProvider {
+    links: Vec<FallbackLink>,
+    resolver: ModelResolver,
+}
+
+impl FallbackProvider {
+    pub fn new(links: Vec<FallbackLink>) -> Self {
+        FallbackProvider { links, resolver: ModelResolver::default() }
+    }
+
+    /// Pick each step's model with `resolver` when neither the request nor the step names one
+    pub fn with_resolver(mut self, resolver: ModelResolver) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// A chain of one, so single providers and chains can be driven the same way
+    pub fn single(provider: Provider) -> Self {
+        Self::new(vec![FallbackLink::new(provider)])
+    }
+
+    pub fn push(&mut self, link: FallbackLink) {
+        self.links.push(link);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Errors worth trying the next provider for. A model one provider lacks may exist on the next.
+    fn should_fall_back(error: &AIProviderError) -> bool {
+        error.is_retryable() || error.kind() == AIErrorKind::ModelNotFound
+    }
+
+    /// The error to report once no provider is left to try
+    fn exhausted(mut attempts: Vec<FallbackAttempt>, last_error: Option<AIProviderError>) -> AIProviderError {
+        match (attempts.len(), last_error) {
+            (0, _) | (_, None) => AIProviderError::InvalidRequest("The fallback chain has no providers".to_string()),
+            (1, Some(error)) => error,
+            (_, Some(_)) => {
+                let summary: Vec<String> = attempts.drain(..)
+                    .map(|a| format!("{} ({}): {}", a.provider, a.model, a.error))
+                    .collect();
+                AIProviderError::APIError(format!("Every provider in the fallback chain failed: {}", summary.join("; ")))
+            },
+        }
+    }
+
+    /// Resolve the model to use on `link`, then `send` the request for it. A failure comes with
+    /// the model it was for, as far as one was picked.
+    async fn try_link<T, F, Fut>(&self, link: &FallbackLink, request: &ChatCompletionRequest, send: F) -> Result<(T, ResolvedModel), (String, AIProviderError)>
+    where
+        F: FnOnce(ChatCompletionRequest) -> Fut,
+        Fut: Future<Output = Result<T, AIProviderError>>,
+    {
+        let explicit = link.explicit_model(&request.model);
+        let resolved = self.resolver.resolve(&link.provider, explicit.as_deref()).await
+            .map_err(|e| (explicit.unwrap_or_default(), e))?;
+
+        let link_request = ChatCompletionRequest { model: resolved.model.id.clone(), ..request.clone() };
+        match send(link_request).await {
+            Ok(value) => Ok((value, resolved)),
+            Err(e) => Err((resolved.model.id, e)),
+        }
+    }
+
+    /// Complete `request` on the first provider that can, reporting which one answered and
+    /// with which model. An empty `request.model` leaves the choice to the resolver.
+    pub async fn complete(&self, request: &ChatCompletionRequest) -> Result<FallbackResponse, AIProviderError> {
+        let mut attempts = Vec::new();
+        let mut last_error = None;
+
+        for link in &self.links {
+            let provider = link.provider.get_provider_name();
+            let send = |link_request: ChatCompletionRequest| async move {
+                link.provider.create_chat_completion(&link_request).await
+            };
+
+            match self.try_link(link, request, send).await {
+                Ok((response, resolved)) => {
+                    let model = resolved.model.id.clone();
+                    return Ok(FallbackResponse { response, provider, model, resolved, attempts });
+                },
+                Err((model, e)) if Self::should_fall_back(&e) => {
+                    log::warn!("{} failed, trying the next provider: {}", provider, e);
+                    attempts.push(FallbackAttempt { provider, model, error: e.to_string() });
+                    last_error = Some(e);
+                },
+                Err((_, e)) => return Err(e),
+            }
+        }
+
+        Err(Self::exhausted(attempts, last_error))
+    }
+
+    /// Open a stream on the first provider that can, returning its name alongside
+    pub async fn open_stream(&self, request: &ChatCompletionRequest) -> Result<(ChatCompletionStream, String), AIProviderError> {
+        let mut attempts = Vec::new();
+        let mut last_error = None;
+
+        for link in &self.links {
+            let provider = link.provider.get_provider_name();
+            let send = |link_request: ChatCompletionRequest| async move {
+                link.provider.create_streaming_chat_completion(&link_request).await
+            };
+
+            match self.try_link(link, request, send).await {
+                Ok((stream, _)) => return Ok((stream, provider)),
+                Err((model, e)) if Self::should_fall_back(&e) => {
+                    log::warn!("{} failed, trying the next provider: {}", provider, e);
+                    attempts.push(FallbackAttempt { provider, model, error: e.to_string() });
+                    last_error = Some(e);
+                },
+                Err((_, e)) => return Err(e),
+            }
+        }
+
+        Err(Self::exhausted(attempts, last_error))
+    }
+}
+
+#[async_trait]
+impl ModelProvider for FallbackProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        // Every reachable provider's models; providers that are down are left out
+        let mut models = Vec::new();
+        for link in &self.links {
+            match link.provider.list_models().await {
+                Ok(link_models) => models.extend(link_models),
+                Err(e) => log::warn!("Couldn't list models for {}: {}", link.provider.get_provider_name(), e),
+            }
+        }
+        Ok(models)
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        for link in &self.links {
+            if let Ok(model) = link.provider.get_model(&link.model_for(model_id)).await {
+                return Ok(model);
+            }
+        }
+        Err(AIProviderError::ModelNotAvailable(model_id.to_string()))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let mut last_error = None;
+        for link in &self.links {
+            match link.provider.get_preferred_inference_model(&link.model_for(preference_model)).await {
+                Ok(model) => return Ok(model),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| AIProviderError::ModelNotAvailable(preference_model.to_string())))
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        for link in &mut self.links {
+            let model = link.model_for(&model_name);
+            link.provider.set_preferred_inference_model(model)?;
+        }
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "fallback".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.links.first().and_then(|link| link.provider.get_preferred_model_name())
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for FallbackProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        self.complete(request).await.map(|answer| answer.response)
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.open_stream(request).await.map(|(stream, _)| stream)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for FallbackProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        // Embeddings from different models aren't comparable, so only the first provider is used
+        match self.links.first() {
+            Some(link) => link.provider.create_embeddings(embedding_request).await,
+            None => Err(AIProviderError::InvalidRequest("The fallback chain has no providers".to_string())),
+        }
+    }
+}
+
+impl PreferredEmbeddingModel for FallbackProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        self.links.first()
+            .map(|link| link.provider.get_preferred_embedding_model())
+            .unwrap_or_default()
+    }
+}
+
+impl AIProvider for FallbackProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::mock_provider::test_support::{mock, request};
+    use crate::ai::resolver::ModelSource;
+
+    #[tokio::test]
+    async fn falls_back_on_a_retryable_error() {
+        let chain = FallbackProvider::new(vec![
+            FallbackLink::new(mock("responses: [{error: {kind: RateLimitExceeded}}]")),
+            FallbackLink::new(mock("default_response: Answered by the second")),
+        ]);
+
+        let answer = chain.complete(&request("mock-model", "Hello")).await.unwrap();
+
+        assert_eq!(answer.response.choices[0].message.content, "Answered by the second");
+        assert_eq!(answer.model, "mock-model");
+        assert_eq!(answer.resolved.source, ModelSource::Explicit);
+        assert_eq!(answer.attempts.len(), 1);
+        assert_eq!(answer.attempts[0].model, "mock-model");
+    }
+
+    #[tokio::test]
+    async fn stops_at_an_invalid_request() {
+        let chain = FallbackProvider::new(vec![
+            FallbackLink::new(mock("responses: [{error: {kind: InvalidRequest, message: Bad prompt}}]")),
+            FallbackLink::new(mock("default_response: Should not be reached")),
+        ]);
+
+        let error = chain.complete(&request("mock-model", "Hello")).await.unwrap_err();
+
+        assert_eq!(error.kind(), AIErrorKind::InvalidRequest);
+        assert!(error.to_string().contains("Bad prompt"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_a_provider_lacks_the_model() {
+        let chain = FallbackProvider::new(vec![
+            FallbackLink::new(mock("models: [{id: small-model}]")),
+            FallbackLink::new(mock("{models: [{id: big-model}], default_response: Answered by big-model}")),
+        ]);
+
+        let answer = chain.complete(&request("big-model", "Hello")).await.unwrap();
+
+        assert_eq!(answer.response.choices[0].message.content, "Answered by big-model");
+        assert_eq!(answer.attempts.len(), 1);
+        assert_eq!(answer.attempts[0].model, "big-model");
+    }
+
+    #[tokio::test]
+    async fn model_map_renames_the_model_per_link() {
+        let mut chain = FallbackProvider::single(mock("responses: [{error: {kind: APIError, message: Overloaded}}]"));
+        chain.push(FallbackLink {
+            provider: mock("models: [{id: local-model}]"),
+            model: None,
+            model_map: HashMap::from([("mock-model".to_string(), "local-model".to_string())]),
+        });
+
+        let answer = chain.complete(&request("mock-model", "Hello")).await.unwrap();
+
+        assert_eq!(answer.model, "local-model");
+        assert_eq!(answer.response.model, "local-model");
+    }
+
+    #[tokio::test]
+    async fn chain_resolves_the_model_when_the_request_names_none() {
+        let chain = FallbackProvider::single(mock("models: [{id: chat-model}]"))
+            .with_resolver(ModelResolver::new(Some("missing-model".to_string())));
+
+        let answer = chain.complete(&request("", "Hello")).await.unwrap();
+
+        assert_eq!(answer.model, "chat-model");
+        assert_eq!(answer.resolved.source, ModelSource::CatalogFallback);
+    }
+}
@@ -0,0 +1,504 @@
+use crate::ai::{
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, ChatCompletionStream},
+    models::*,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::{Client as HttpClient, header};
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use super::sse::sse_data_stream;
+use super::connection::ConnectionSettings;
+use super::http_error::check_response;
+
+/// Google's Generative Language API endpoint
+pub const GEMINI_DEFAULT_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// Name used in errors
+const PROVIDER_NAME: &str = "Gemini";
+
+/// Google Gemini provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiProvider {
+    #[serde(skip)]
+    client: HttpClient,
+    #[serde(skip)]
+    api_key: String,
+    base_url: String,
+    preferred_model_name: Option<String>,
+    /// Kept so the client can be rebuilt when the connection settings change
+    #[serde(skip)]
+    default_headers: header::HeaderMap,
+    #[serde(skip)]
+    connection: ConnectionSettings,
+}
+
+impl GeminiProvider {
+    /// Create a new Gemini provider talking to the public API
+    pub fn new(api_key: &str) -> Self {
+        Self::with_base_url(api_key, GEMINI_DEFAULT_URL)
+    }
+
+    /// Create a provider for another base URL, such as a proxy
+    pub fn with_base_url(api_key: &str, base_url: &str) -> Self {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        let connection = ConnectionSettings::default();
+        let client = connection.build_client(headers.clone())
+            .expect("Failed to create HTTP client");
+
+        GeminiProvider {
+            client,
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            preferred_model_name: None,
+            default_headers: headers,
+            connection,
+        }
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.client = connection.build_client(self.default_headers.clone())?;
+        self.connection = connection;
+        Ok(())
+    }
+
+    fn add_auth_header(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder.header("x-goog-api-key", &self.api_key)
+    }
+
+    /// URL for a model method such as `generateContent`
+    fn model_url(&self, model: &str, method: &str) -> String {
+        format!("{}/models/{}:{}", self.base_url, strip_models_prefix(model), method)
+    }
+
+    async fn post<T: Serialize>(&self, url: &str, body: &T) -> Result<reqwest::Response, AIProviderError> {
+        log::debug!("Gemini request URL: {}", url);
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.post(url).json(body))).await?;
+
+        check_response(PROVIDER_NAME, response).await
+    }
+}
+
+/// Gemini names models `models/gemini-...`; we use the bare id everywhere else
+fn strip_models_prefix(model: &str) -> &str {
+    model.strip_prefix("models/").unwrap_or(model)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GeminiPart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GeminiContent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<String>,
+    #[serde(default)]
+    parts: Vec<GeminiPart>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct GeminiGenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct GeminiRequest {
+    contents: Vec<GeminiContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<GeminiContent>,
+    generation_config: GeminiGenerationConfig,
+}
+
+impl GeminiRequest {
+    /// System messages become `systemInstruction` and the assistant speaks as `model`
+    fn from_request(request: &ChatCompletionRequest) -> Result<Self, AIProviderError> {
+        let mut system_parts = Vec::new();
+        let mut contents = Vec::new();
+
+        for msg in &request.messages {
+            let role = match msg.role {
+                MessageRole::System => {
+                    system_parts.push(GeminiPart { text: msg.content.clone() });
+                    continue;
+                },
+                MessageRole::User => "user",
+                MessageRole::Assistant => "model",
+                MessageRole::Tool | MessageRole::Function => {
+                    return Err(AIProviderError::InvalidRequest(
+                        "Tool and Function messages are not supported yet".to_string()
+                    ));
+                }
+            };
+            contents.push(GeminiContent {
+                role: Some(role.to_string()),
+                parts: vec![GeminiPart { text: msg.content.clone() }],
+            });
+        }
+
+        Ok(GeminiRequest {
+            contents,
+            system_instruction: if system_parts.is_empty() {
+                None
+            } else {
+                Some(GeminiContent { role: None, parts: system_parts })
+            },
+            generation_config: GeminiGenerationConfig {
+                temperature: request.temperature,
+                top_p: request.top_p,
+                max_output_tokens: request.max_tokens,
+                stop_sequences: request.stop.clone(),
+                seed: request.seed,
+            },
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiCandidate {
+    content: Option<GeminiContent>,
+    finish_reason: Option<String>,
+    #[serde(default)]
+    index: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiUsage {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    total_token_count: u32,
+}
+
+impl From<GeminiUsage> for TokenUsage {
+    fn from(usage: GeminiUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_token_count,
+            completion_tokens: usage.candidates_token_count,
+            total_tokens: usage.total_token_count,
+        }
+    }
+}
+
+// Both generateContent and each streamed event use this shape
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<GeminiCandidate>,
+    usage_metadata: Option<GeminiUsage>,
+    model_version: Option<String>,
+    response_id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiModelData {
+    name: String,
+    display_name: Option<String>,
+    input_token_limit: Option<usize>,
+    output_token_limit: Option<usize>,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiModelList {
+    #[serde(default)]
+    models: Vec<GeminiModelData>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Serialize)]
+struct GeminiEmbedRequest {
+    model: String,
+    content: GeminiContent,
+}
+
+#[derive(Serialize)]
+struct GeminiBatchEmbedRequest {
+    requests: Vec<GeminiEmbedRequest>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbeddingValues {
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GeminiBatchEmbedResponse {
+    #[serde(default)]
+    embeddings: Vec<GeminiEmbeddingValues>,
+}
+
+/// Map Gemini's finish reasons onto the OpenAI style ones the rest of the app expects
+fn convert_finish_reason(finish_reason: &str) -> String {
+    match finish_reason {
+        "STOP" => "stop".to_string(),
+        "MAX_TOKENS" => "length".to_string(),
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII" => "content_filter".to_string(),
+        other => other.to_lowercase(),
+    }
+}
+
+fn candidate_text(candidate: &GeminiCandidate) -> String {
+    candidate.content.as_ref()
+        .map(|content| content.parts.iter().map(|part| part.text.as_str()).collect::<String>())
+        .unwrap_or_default()
+}
+
+fn convert_gemini_response(response: GeminiResponse, requested_model: &str) -> ChatCompletionResponse {
+    ChatCompletionResponse {
+        id: response.response_id.clone().unwrap_or_else(|| Uuid::new_v4().to_string()),
+        choices: response.candidates.iter()
+            .map(|candidate| ChatCompletionChoice {
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: candidate_text(candidate),
+                    name: None,
+                },
+                finish_reason: candidate.finish_reason.as_deref().map(convert_finish_reason),
+                index: candidate.index,
+            })
+            .collect(),
+        created: chrono::Utc::now().timestamp() as u64,
+        model: response.model_version.unwrap_or_else(|| requested_model.to_string()),
+        usage: response.usage_metadata.map(TokenUsage::from),
+    }
+}
+
+fn convert_gemini_stream_event(id: &str, created: u64, data: &str) -> Result<ChatCompletionChunk, AIProviderError> {
+    let response: GeminiResponse = serde_json::from_str(data)
+        .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse stream event: {}: {}", e, data)))?;
+
+    let choices: Vec<ChatCompletionChunkChoice> = response.candidates.iter()
+        .map(|candidate| {
+            let text = candidate_text(candidate);
+            ChatCompletionChunkChoice {
+                delta: ChatMessageDelta {
+                    role: Some(MessageRole::Assistant),
+                    content: if text.is_empty() { None } else { Some(text) },
+                },
+                finish_reason: candidate.finish_reason.as_deref().map(convert_finish_reason),
+                index: candidate.index,
+            }
+        })
+        .collect();
+
+    // usageMetadata is a running total on every event; only report it once we're finished
+    let finished = choices.iter().any(|choice| choice.finish_reason.is_some());
+
+    Ok(ChatCompletionChunk {
+        id: id.to_string(),
+        choices,
+        created,
+        usage: if finished { response.usage_metadata.map(TokenUsage::from) } else { None },
+    })
+}
+
+fn convert_gemini_model(model: GeminiModelData) -> AIModel {
+    let mut capabilities = Vec::new();
+    if model.supported_generation_methods.iter().any(|m| m == "generateContent") {
+        capabilities.push(ModelCapability::ChatCompletion);
+    }
+    if model.supported_generation_methods.iter().any(|m| m == "embedContent") {
+        capabilities.push(ModelCapability::Embedding);
+    }
+
+    let id = strip_models_prefix(&model.name).to_string();
+
+    AIModel {
+        id: id.clone(),
+        name: id,
+        provider: "gemini".to_string(),
+        capabilities,
+        context_length: model.input_token_limit,
+        max_output_tokens: None,
+        pricing: None,
+        deprecation_date: None,
+        additional_info: serde_json::to_value(model).unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl ModelProvider for GeminiProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        // The list is paged; keep going until there's no next page
+        loop {
+            let mut url = format!("{}/models?pageSize=1000", self.base_url);
+            if let Some(token) = &page_token {
+                url.push_str(&format!("&pageToken={}", token));
+            }
+
+            let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.get(&url))).await?;
+            let response = check_response(PROVIDER_NAME, response).await?;
+
+            let model_list: GeminiModelList = response.json().await
+                .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+            models.extend(model_list.models.into_iter().map(convert_gemini_model));
+
+            match model_list.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => break,
+            }
+        }
+
+        Ok(models)
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        let url = format!("{}/models/{}", self.base_url, strip_models_prefix(model_id));
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.get(&url))).await?;
+        let response = check_response(PROVIDER_NAME, response).await?;
+
+        let model: GeminiModelData = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(convert_gemini_model(model))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        let all_models = self.list_models().await?;
+
+        if let Some(model) = all_models.iter().find(|m| m.id == strip_models_prefix(&model_id)) {
+            return Ok(model.clone());
+        }
+
+        for candidate in ["gemini-2.0-flash", "gemini-1.5-flash", "gemini-1.5-pro"] {
+            if let Some(model) = all_models.iter().find(|m| m.id == candidate) {
+                log::warn!("Model '{}' not found, using {}", model_id, model.name);
+                return Ok(model.clone());
+            }
+        }
+
+        all_models.into_iter()
+            .find(|m| m.capabilities.contains(&ModelCapability::ChatCompletion))
+            .ok_or_else(|| AIProviderError::ModelNotAvailable("No chat models available for Gemini provider".to_string()))
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "gemini".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for GeminiProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let body = GeminiRequest::from_request(request)?;
+        let url = self.model_url(&request.model, "generateContent");
+        let response = self.post(&url, &body).await?;
+
+        let gemini_response: GeminiResponse = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(convert_gemini_response(gemini_response, &request.model))
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let body = GeminiRequest::from_request(request)?;
+        let url = format!("{}?alt=sse", self.model_url(&request.model, "streamGenerateContent"));
+        let response = self.post(&url, &body).await?;
+
+        let id = Uuid::new_v4().to_string();
+        let created = chrono::Utc::now().timestamp() as u64;
+
+        let stream = sse_data_stream(PROVIDER_NAME, response, self.connection.idle_timeout())
+            .map(move |data| data.and_then(|data| convert_gemini_stream_event(&id, created, &data)));
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for GeminiProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        let model_name = format!("models/{}", strip_models_prefix(&embedding_request.model));
+
+        let body = GeminiBatchEmbedRequest {
+            requests: embedding_request.input.iter()
+                .map(|text| GeminiEmbedRequest {
+                    model: model_name.clone(),
+                    content: GeminiContent {
+                        role: None,
+                        parts: vec![GeminiPart { text: text.clone() }],
+                    },
+                })
+                .collect(),
+        };
+
+        let url = self.model_url(&embedding_request.model, "batchEmbedContents");
+        let response = self.post(&url, &body).await?;
+
+        let embed_response: GeminiBatchEmbedResponse = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(embed_response.embeddings.into_iter()
+            .enumerate()
+            .map(|(index, e)| Embedding {
+                vector: e.values,
+                index,
+                model_name: Some(embedding_request.model.clone()),
+            })
+            .collect())
+    }
+}
+
+impl PreferredEmbeddingModel for GeminiProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        "text-embedding-004".to_string()
+    }
+}
+
+impl AIProvider for GeminiProvider {}
@@ -0,0 +1,211 @@
+use crate::ai::traits::{AIErrorKind, AIProviderError, ProviderErrorDetails};
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+
+/// Where providers put their id for a request: OpenAI, Anthropic, Azure
+const REQUEST_ID_HEADERS: [&str; 4] = ["x-request-id", "request-id", "apim-request-id", "x-ms-request-id"];
+
+/// Hand back `response` if its status is good, otherwise the classified error it carries
+pub(crate) async fn check_response(provider: &str, response: reqwest::Response) -> Result<reqwest::Response, AIProviderError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    Err(error_from_response(provider, response).await)
+}
+
+/// Classify a failed response from its status, headers and error body
+pub(crate) async fn error_from_response(provider: &str, response: reqwest::Response) -> AIProviderError {
+    let status = response.status();
+    let request_id = request_id(response.headers());
+    let text = response.text().await
+        .unwrap_or_else(|_| "Failed to read response body".to_string());
+
+    let mut details = details_from_body(provider, Some(status.as_u16()), &text);
+    if details.message.is_empty() {
+        details.message = status.to_string();
+    }
+    details.request_id = request_id;
+    AIProviderError::Provider(Box::new(details))
+}
+
+/// Classify an error body. Understands OpenAI's `{"error": {"message", "type", "code"}}`,
+/// Anthropic's `{"type": "error", "error": {"type", "message"}}`, Gemini's
+/// `{"error": {"code", "message", "status", "details"}}`, Ollama's `{"error": "..."}` and
+/// the `{"detail": ...}` of FastAPI based servers. Anything else is kept as the message.
+pub(crate) fn details_from_body(provider: &str, status: Option<u16>, body: &str) -> ProviderErrorDetails {
+    let json: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let text = |value: &Value| value.as_str().map(str::to_string);
+
+    let (message, error_type, code) = match json.get("error") {
+        Some(Value::String(message)) => (Some(message.clone()), None, None),
+        Some(error @ Value::Object(_)) => {
+            // Gemini's numeric code repeats the HTTP status; the useful code is in details[].reason
+            let reason = error.get("details")
+                .and_then(Value::as_array)
+                .and_then(|details| details.iter().find_map(|detail| text(&detail["reason"])));
+            (
+                text(&error["message"]),
+                text(&error["type"]).or_else(|| text(&error["status"])),
+                text(&error["code"]).or(reason),
+            )
+        },
+        _ => (text(&json["message"]).or_else(|| text(&json["detail"])), None, None),
+    };
+
+    let message = message.unwrap_or_else(|| body.trim().to_string());
+    ProviderErrorDetails {
+        kind: classify(status, code.as_deref(), error_type.as_deref(), &message),
+        provider: provider.to_string(),
+        message,
+        status,
+        code,
+        error_type,
+        request_id: None,
+    }
+}
+
+/// The error for a request that never got a response
+pub(crate) fn network_error(provider: &str, error: &reqwest::Error) -> AIProviderError {
+    let message = format!("Network error: {}", error);
+    let status = error.status().map(|status| status.as_u16());
+    let kind = match status {
+        // An error_for_status() failure: there was a response after all
+        Some(_) => classify(status, None, None, &message),
+        None if error.is_timeout() => AIErrorKind::Timeout,
+        None => AIErrorKind::Network,
+    };
+    let mut details = ProviderErrorDetails::new(kind, provider, message);
+    details.status = status;
+    AIProviderError::Provider(Box::new(details))
+}
+
+pub(crate) fn request_id(headers: &HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS.iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Work out what kind of failure this is. The specific kinds are checked first, since
+/// providers report them under generic statuses: OpenAI's context length errors are plain
+/// 400s and its exhausted quota is a 429.
+pub(crate) fn classify(status: Option<u16>, code: Option<&str>, error_type: Option<&str>, message: &str) -> AIErrorKind {
+    let tags: Vec<String> = [code, error_type].into_iter().flatten().map(str::to_lowercase).collect();
+    let tagged = |needles: &[&str]| tags.iter().any(|tag| needles.iter().any(|needle| tag.contains(needle)));
+    let message = message.to_lowercase();
+    let says = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+
+    if tagged(&["context_length", "string_above_max_length"])
+        || says(&["maximum context length", "context length", "context window", "prompt is too long", "too many tokens"]) {
+        return AIErrorKind::ContextLengthExceeded;
+    }
+    if tagged(&["content_filter", "content_policy", "safety", "blocked"])
+        || says(&["content management policy", "content filter", "safety reasons"]) {
+        return AIErrorKind::ContentFiltered;
+    }
+    // Gemini words its per-minute limits like an exhausted quota, so RESOURCE_EXHAUSTED stays a rate limit
+    if tagged(&["insufficient_quota", "billing"])
+        || says(&["credit balance is too low"])
+        || (says(&["exceeded your current quota"]) && !tagged(&["resource_exhausted"])) {
+        return AIErrorKind::QuotaExhausted;
+    }
+    if matches!(status, Some(401) | Some(403))
+        || tagged(&["authentication", "permission", "invalid_api_key", "api_key_invalid", "unauthenticated"]) {
+        return AIErrorKind::Auth;
+    }
+    if status == Some(429)
+        || tagged(&["rate_limit", "resource_exhausted"])
+        || tags.iter().any(|tag| tag == "requests" || tag == "tokens") {
+        return AIErrorKind::RateLimited;
+    }
+    // A bare 404 may just as well be a wrong base URL, so the error has to be about the model
+    if tagged(&["model_not_found"])
+        || (says(&["model"]) && (status == Some(404) || tagged(&["not_found"]) || says(&["not found", "does not exist"]))) {
+        return AIErrorKind::ModelNotFound;
+    }
+    if status == Some(408) || tagged(&["timeout", "deadline_exceeded"]) {
+        return AIErrorKind::Timeout;
+    }
+    if status.is_some_and(|status| status >= 500) || tagged(&["server_error", "overloaded", "internal", "unavailable"]) {
+        return AIErrorKind::Server;
+    }
+    if status.is_some_and(|status| (400..500).contains(&status)) || tagged(&["invalid_request", "invalid_argument"]) {
+        return AIErrorKind::InvalidRequest;
+    }
+    AIErrorKind::Other
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_bodies_are_parsed_and_classified() {
+        // (status, body, kind, message, error type, code)
+        let cases = [
+            // OpenAI
+            (404, r#"{"error": {"message": "The model `gpt-9` does not exist or you do not have access to it.", "type": "invalid_request_error", "param": null, "code": "model_not_found"}}"#,
+                AIErrorKind::ModelNotFound, "The model `gpt-9` does not exist or you do not have access to it.", Some("invalid_request_error"), Some("model_not_found")),
+            (404, r#"{"error": {"message": "Invalid URL (POST /v1/chat/completion)", "type": "invalid_request_error", "param": null, "code": null}}"#,
+                AIErrorKind::InvalidRequest, "Invalid URL (POST /v1/chat/completion)", Some("invalid_request_error"), None),
+            (400, r#"{"error": {"message": "This model's maximum context length is 128000 tokens.", "type": "invalid_request_error", "code": "context_length_exceeded"}}"#,
+                AIErrorKind::ContextLengthExceeded, "This model's maximum context length is 128000 tokens.", Some("invalid_request_error"), Some("context_length_exceeded")),
+            (429, r#"{"error": {"message": "You exceeded your current quota, please check your plan and billing details.", "type": "insufficient_quota", "code": "insufficient_quota"}}"#,
+                AIErrorKind::QuotaExhausted, "You exceeded your current quota, please check your plan and billing details.", Some("insufficient_quota"), Some("insufficient_quota")),
+            (429, r#"{"error": {"message": "Rate limit reached for gpt-4o on tokens per min.", "type": "tokens", "code": "rate_limit_exceeded"}}"#,
+                AIErrorKind::RateLimited, "Rate limit reached for gpt-4o on tokens per min.", Some("tokens"), Some("rate_limit_exceeded")),
+            (401, r#"{"error": {"message": "Incorrect API key provided: sk-abc.", "type": "invalid_request_error", "code": "invalid_api_key"}}"#,
+                AIErrorKind::Auth, "Incorrect API key provided: sk-abc.", Some("invalid_request_error"), Some("invalid_api_key")),
+            // Anthropic
+            (404, r#"{"type": "error", "error": {"type": "not_found_error", "message": "model: claude-nope"}}"#,
+                AIErrorKind::ModelNotFound, "model: claude-nope", Some("not_found_error"), None),
+            (404, r#"{"type": "error", "error": {"type": "not_found_error", "message": "Not Found"}}"#,
+                AIErrorKind::InvalidRequest, "Not Found", Some("not_found_error"), None),
+            (529, r#"{"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}"#,
+                AIErrorKind::Server, "Overloaded", Some("overloaded_error"), None),
+            (400, r#"{"type": "error", "error": {"type": "invalid_request_error", "message": "Your credit balance is too low to access the Anthropic API."}}"#,
+                AIErrorKind::QuotaExhausted, "Your credit balance is too low to access the Anthropic API.", Some("invalid_request_error"), None),
+            // Gemini
+            (404, r#"{"error": {"code": 404, "message": "models/gemini-9 is not found for API version v1beta.", "status": "NOT_FOUND"}}"#,
+                AIErrorKind::ModelNotFound, "models/gemini-9 is not found for API version v1beta.", Some("NOT_FOUND"), None),
+            (400, r#"{"error": {"code": 400, "message": "API key not valid. Please pass a valid API key.", "status": "INVALID_ARGUMENT", "details": [{"@type": "type.googleapis.com/google.rpc.ErrorInfo", "reason": "API_KEY_INVALID"}]}}"#,
+                AIErrorKind::Auth, "API key not valid. Please pass a valid API key.", Some("INVALID_ARGUMENT"), Some("API_KEY_INVALID")),
+            (429, r#"{"error": {"code": 429, "message": "You exceeded your current quota, please check your plan and billing details.", "status": "RESOURCE_EXHAUSTED"}}"#,
+                AIErrorKind::RateLimited, "You exceeded your current quota, please check your plan and billing details.", Some("RESOURCE_EXHAUSTED"), None),
+            (503, r#"{"error": {"code": 503, "message": "The model is overloaded. Please try again later.", "status": "UNAVAILABLE"}}"#,
+                AIErrorKind::Server, "The model is overloaded. Please try again later.", Some("UNAVAILABLE"), None),
+            // Ollama
+            (404, r#"{"error": "model \"llama9\" not found, try pulling it first"}"#,
+                AIErrorKind::ModelNotFound, "model \"llama9\" not found, try pulling it first", None, None),
+            (500, r#"{"error": "llama runner process has terminated: exit status 2"}"#,
+                AIErrorKind::Server, "llama runner process has terminated: exit status 2", None, None),
+            // FastAPI based servers, vLLM and friends
+            (404, r#"{"detail": "Not Found"}"#,
+                AIErrorKind::InvalidRequest, "Not Found", None, None),
+            (404, r#"{"object": "error", "message": "The model `qwen9` does not exist.", "type": "NotFoundError", "param": null, "code": 404}"#,
+                AIErrorKind::ModelNotFound, "The model `qwen9` does not exist.", None, None),
+            // Not JSON at all
+            (502, "<html>Bad Gateway</html>",
+                AIErrorKind::Server, "<html>Bad Gateway</html>", None, None),
+        ];
+
+        for (status, body, kind, message, error_type, code) in cases {
+            let details = details_from_body("Test", Some(status), body);
+            assert_eq!(details.kind, kind, "{}", body);
+            assert_eq!(details.message, message);
+            assert_eq!(details.error_type.as_deref(), error_type, "{}", body);
+            assert_eq!(details.code.as_deref(), code, "{}", body);
+            assert_eq!(details.status, Some(status));
+        }
+    }
+
+    #[test]
+    fn classify_reads_the_message_when_there_is_no_status() {
+        assert_eq!(classify(None, None, None, "model 'llama9' not found"), AIErrorKind::ModelNotFound);
+        assert_eq!(classify(None, None, None, "prompt is too long"), AIErrorKind::ContextLengthExceeded);
+        assert_eq!(classify(None, Some("rate_limit_exceeded"), None, "Slow down"), AIErrorKind::RateLimited);
+        assert_eq!(classify(None, None, Some("DEADLINE_EXCEEDED"), "Deadline expired"), AIErrorKind::Timeout);
+        assert_eq!(classify(None, None, None, "Something odd happened"), AIErrorKind::Other);
+        assert_eq!(classify(Some(404), None, None, "Not Found"), AIErrorKind::InvalidRequest);
+    }
+}
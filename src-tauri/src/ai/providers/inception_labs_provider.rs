@@ -1,34 +1,406 @@
-use crate::ai::traits::{ChatCompletionProvider, DiffusingProvider, ModelProvider, EmbeddingProvider, AIProviderError};
+use crate::ai::traits::{ChatCompletionProvider, DiffusingProvider, ModelProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, ChatCompletionStream};
 use crate::ai::models::*;
 use async_trait::async_trait;
-use futures::Stream;
+use futures::StreamExt;
+use reqwest::{Client as HttpClient, header};
 use serde::{Serialize, Deserialize};
+use super::sse::sse_data_stream;
+use super::connection::ConnectionSettings;
+use super::http_error::check_response;
 
+/// Inception Labs' OpenAI compatible endpoint
+pub const INCEPTION_LABS_DEFAULT_URL: &str = "https://api.inceptionlabs.ai/v1";
+
+/// Name used in errors
+const PROVIDER_NAME: &str = "Inception Labs";
+
+/// Provider for Inception Labs' diffusion LLMs (Mercury)
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InceptionLabsProvider {
+    #[serde(skip)]
+    client: HttpClient,
+    #[serde(skip_serializing)]
     pub api_key: String,
     pub api_url: String,
+    preferred_model_name: Option<String>,
+    /// Kept so the client can be rebuilt when the connection settings change
+    #[serde(skip)]
+    default_headers: header::HeaderMap,
+    #[serde(skip)]
+    connection: ConnectionSettings,
 }
 
 impl InceptionLabsProvider {
+    /// Create a new Inception Labs provider. An empty `api_url` uses the public endpoint.
     pub fn new(api_key: String, api_url: String) -> Self {
-        Self { api_key, api_url }
+        let mut headers = header::HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        let connection = ConnectionSettings::default();
+        let client = connection.build_client(headers.clone())
+            .expect("Failed to create HTTP client");
+
+        let api_url = if api_url.is_empty() {
+            INCEPTION_LABS_DEFAULT_URL.to_string()
+        } else {
+            api_url.trim_end_matches('/').to_string()
+        };
+
+        Self {
+            client,
+            api_key,
+            api_url,
+            preferred_model_name: None,
+            default_headers: headers,
+            connection,
+        }
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.client = connection.build_client(self.default_headers.clone())?;
+        self.connection = connection;
+        Ok(())
+    }
+
+    fn add_auth_header(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        builder.header("Authorization", format!("Bearer {}", self.api_key))
+    }
+
+    /// POST a chat request and hand back the response if the status is good
+    async fn send_chat_request(&self, body: &InceptionRequest) -> Result<reqwest::Response, AIProviderError> {
+        let url = format!("{}/chat/completions", self.api_url);
+        log::debug!("Inception Labs request URL: {}", url);
+
+        let http_request = self.add_auth_header(self.client.post(&url).json(body));
+        let response = self.connection.send(PROVIDER_NAME, http_request).await?;
+
+        check_response(PROVIDER_NAME, response).await
+    }
+
+    /// Open a streaming chat request and map every SSE frame to a chunk
+    async fn open_stream(
+        &self,
+        request: &ChatCompletionRequest,
+        diffusing: bool,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let body = InceptionRequest::from_request(request, true, diffusing);
+        let response = self.send_chat_request(&body).await?;
+
+        let stream = sse_data_stream(PROVIDER_NAME, response, self.connection.idle_timeout())
+            .map(|data| data.and_then(|data| convert_inception_stream_chunk(&data)));
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct InceptionMessage {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize, Debug)]
+struct InceptionRequest {
+    model: String,
+    messages: Vec<InceptionMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    /// When streaming, send the whole refined text with every frame instead of deltas
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    diffusing: bool,
+}
+
+impl InceptionRequest {
+    fn from_request(request: &ChatCompletionRequest, stream: bool, diffusing: bool) -> Self {
+        InceptionRequest {
+            model: request.model.clone(),
+            messages: request.messages.iter()
+                .map(|msg| InceptionMessage {
+                    role: role_to_inception(&msg.role),
+                    content: msg.content.clone(),
+                })
+                .collect(),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            stop: request.stop.clone(),
+            stream,
+            diffusing,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct InceptionUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+impl From<InceptionUsage> for TokenUsage {
+    fn from(usage: InceptionUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct InceptionResponseMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InceptionResponseChoice {
+    message: InceptionResponseMessage,
+    finish_reason: Option<String>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct InceptionResponse {
+    id: String,
+    created: u64,
+    model: String,
+    choices: Vec<InceptionResponseChoice>,
+    usage: Option<InceptionUsage>,
+}
+
+#[derive(Deserialize)]
+struct InceptionStreamDelta {
+    role: Option<String>,
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InceptionStreamChoice {
+    delta: InceptionStreamDelta,
+    finish_reason: Option<String>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct InceptionStreamChunk {
+    id: String,
+    created: u64,
+    #[serde(default)]
+    choices: Vec<InceptionStreamChoice>,
+    usage: Option<InceptionUsage>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct InceptionModelData {
+    id: String,
+    created: Option<u64>,
+    owned_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct InceptionModelList {
+    data: Vec<InceptionModelData>,
+}
+
+fn role_to_inception(role: &MessageRole) -> String {
+    match role {
+        MessageRole::System => "system".to_string(),
+        MessageRole::User => "user".to_string(),
+        MessageRole::Assistant => "assistant".to_string(),
+        MessageRole::Tool => "tool".to_string(),
+        MessageRole::Function => "function".to_string(),
+    }
+}
+
+fn role_from_inception(role: &str) -> MessageRole {
+    match role {
+        "system" => MessageRole::System,
+        "user" => MessageRole::User,
+        "tool" => MessageRole::Tool,
+        "function" => MessageRole::Function,
+        _ => MessageRole::Assistant,
+    }
+}
+
+/// Convert one streamed `data:` payload into our generic chunk format
+fn convert_inception_stream_chunk(data: &str) -> Result<ChatCompletionChunk, AIProviderError> {
+    let chunk: InceptionStreamChunk = serde_json::from_str(data)
+        .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse stream chunk: {}: {}", e, data)))?;
+
+    Ok(ChatCompletionChunk {
+        id: chunk.id,
+        created: chunk.created,
+        choices: chunk.choices.into_iter()
+            .map(|choice| ChatCompletionChunkChoice {
+                delta: ChatMessageDelta {
+                    role: choice.delta.role.as_deref().map(role_from_inception),
+                    content: choice.delta.content,
+                },
+                finish_reason: choice.finish_reason,
+                index: choice.index,
+            })
+            .collect(),
+        usage: chunk.usage.map(TokenUsage::from),
+    })
+}
+
+fn convert_inception_model(model: InceptionModelData) -> AIModel {
+    AIModel {
+        id: model.id.clone(),
+        name: model.id.clone(),
+        provider: "inception_labs".to_string(),
+        capabilities: vec![ModelCapability::ChatCompletion],
+        context_length: None, // Not provided by the API
+        max_output_tokens: None,
+        pricing: None,
+        deprecation_date: None,
+        additional_info: serde_json::to_value(model).unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl ModelProvider for InceptionLabsProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        let url = format!("{}/models", self.api_url);
+
+        let response = self.connection.send(PROVIDER_NAME, self.add_auth_header(self.client.get(&url))).await?;
+        let response = check_response(PROVIDER_NAME, response).await?;
+
+        let model_list: InceptionModelList = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(model_list.data.into_iter().map(convert_inception_model).collect())
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        self.list_models().await?
+            .into_iter()
+            .find(|m| m.id == model_id)
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(model_id.to_string()))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        let all_models = self.list_models().await?;
+
+        if let Some(model) = all_models.iter().find(|m| m.id == model_id) {
+            return Ok(model.clone());
+        }
+
+        // Mercury is the general purpose model, mercury-coder the code model
+        for candidate in ["mercury", "mercury-coder"] {
+            if let Some(model) = all_models.iter().find(|m| m.id == candidate) {
+                log::warn!("Model '{}' not found, using {}", model_id, model.name);
+                return Ok(model.clone());
+            }
+        }
+
+        all_models.into_iter().next()
+            .ok_or_else(|| AIProviderError::ModelNotAvailable("No models available for Inception Labs provider".to_string()))
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "inception_labs".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
     }
 }
 
-// Implement the traits as needed (ChatCompletionProvider, ModelProvider, etc.)
-// For brevity, only DiffusingProvider is shown here
+#[async_trait]
+impl ChatCompletionProvider for InceptionLabsProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let body = InceptionRequest::from_request(request, false, false);
+        let response = self.send_chat_request(&body).await?;
+
+        let inception_response: InceptionResponse = response.json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(ChatCompletionResponse {
+            id: inception_response.id,
+            choices: inception_response.choices.into_iter()
+                .map(|choice| ChatCompletionChoice {
+                    message: ChatMessage {
+                        role: role_from_inception(&choice.message.role),
+                        content: choice.message.content.unwrap_or_default(),
+                        name: None,
+                    },
+                    finish_reason: choice.finish_reason,
+                    index: choice.index,
+                })
+                .collect(),
+            created: inception_response.created,
+            model: inception_response.model,
+            usage: inception_response.usage.map(TokenUsage::from),
+        })
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.open_stream(request, false).await
+    }
+}
 
 #[async_trait]
 impl DiffusingProvider for InceptionLabsProvider {
+    /// Each chunk's `delta.content` is the complete, progressively refined text,
+    /// so callers should replace what they have rather than append to it.
     async fn create_diffusing_stream(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<impl Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send, AIProviderError> {
-        // Here, you would:
-        // - Build the HTTP request to the Inception Labs API with stream: true, diffusing: true
-        // - Parse the streaming response into ChatCompletionChunk(s)
-        // - Return a Stream of those chunks
-        // (You can use reqwest's streaming API and futures::stream for this)
-        unimplemented!()
-    }
-}
\ No newline at end of file
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.open_stream(request, true).await
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for InceptionLabsProvider {
+    async fn create_embeddings(
+        &self,
+        _embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        Err(AIProviderError::NotImplemented("Inception Labs does not offer embedding models".to_string()))
+    }
+}
+
+impl PreferredEmbeddingModel for InceptionLabsProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        // Inception Labs doesn't have an embedding model
+        String::new()
+    }
+}
+
+impl AIProvider for InceptionLabsProvider {
+    fn as_diffusing(&self) -> Option<&dyn DiffusingProvider> {
+        Some(self)
+    }
+}
@@ -1,77 +1,155 @@
 use crate::ai::{
-    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProviderError},
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, AIErrorKind, ChatCompletionStream, HealthStatus},
     models::*,
+    resolver::can_chat,
 };
+use super::openai_compatible_provider::{OpenAICompatibleConfig, OpenAICompatibleProvider};
+use super::retry::RetryPolicy;
+use super::connection::ConnectionSettings;
 use async_trait::async_trait;
-use reqwest::{Client as HttpClient, header};
 use serde::{Serialize, Deserialize};
-use serde_json::{json, Value};
-use std::time::{SystemTime, UNIX_EPOCH};
-use futures::{stream, Stream, StreamExt};
-use std::pin::Pin;
-use std::sync::Arc;
-use lazy_static::lazy_static;
-use std::sync::Mutex;
-use async_openai::types::CreateChatCompletionRequest;
-
-lazy_static! {
-    static ref LAST_REQUEST: Mutex<Option<CreateChatCompletionRequest>> = Mutex::new(None);
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Id for `get_provider_name`, the `provider` tag on models and the provider named in errors
+const PROVIDER_ID: &str = "lm_studio";
+
+/// A model as LM Studio's native API describes it, downloaded or loaded
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LMStudioModelInfo {
+    pub id: String,
+    /// "llm", "vlm" or "embeddings"
+    #[serde(rename = "type")]
+    pub model_type: Option<String>,
+    pub publisher: Option<String>,
+    pub arch: Option<String>,
+    /// "gguf" or "mlx"
+    pub compatibility_type: Option<String>,
+    pub quantization: Option<String>,
+    /// "loaded" or "not-loaded"
+    pub state: Option<String>,
+    /// The most context the model supports
+    pub max_context_length: Option<usize>,
+    /// The context it was loaded with, when it's loaded
+    pub loaded_context_length: Option<usize>,
 }
 
-impl LMStudioProvider {
-    fn set_last_request(request: CreateChatCompletionRequest) {
-        let mut last_request = LAST_REQUEST.lock().unwrap();
-        *last_request = Some(request);
+impl LMStudioModelInfo {
+    pub fn is_loaded(&self) -> bool {
+        self.state.as_deref() == Some("loaded")
     }
 
-    fn get_last_request() -> Option<CreateChatCompletionRequest> {
-        let last_request = LAST_REQUEST.lock().unwrap();
-        last_request.clone()
+    fn capabilities(&self) -> Vec<ModelCapability> {
+        match self.model_type.as_deref() {
+            Some("llm") | Some("vlm") => vec![ModelCapability::ChatCompletion],
+            Some("embeddings") => vec![ModelCapability::Embedding],
+            _ => Vec::new(),
+        }
+    }
+
+    /// A loaded model's context window is the one it was loaded with
+    fn to_ai_model(&self) -> AIModel {
+        AIModel {
+            id: self.id.clone(),
+            name: self.id.clone(),
+            provider: "lm_studio".to_string(),
+            capabilities: self.capabilities(),
+            context_length: self.loaded_context_length.filter(|_| self.is_loaded()).or(self.max_context_length),
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: serde_json::to_value(self).unwrap_or_default(),
+        }
     }
 }
-/// LM Studio provider implementation
 
+/// Without the native API there's no telling what a model can do; `/v1/models` only lists ids,
+/// so leave the capabilities to the model catalog
+fn without_guessed_capabilities(mut model: AIModel) -> AIModel {
+    model.capabilities.clear();
+    model
+}
+
+/// Servers before LM Studio 0.3.6 have no native API and answer 404
+fn is_missing_endpoint(error: &AIProviderError) -> bool {
+    error.details().and_then(|details| details.status) == Some(404)
+}
+
+/// LM Studio provider implementation.
+///
+/// LM Studio serves the OpenAI wire format, so this is a thin wrapper around
+/// `OpenAICompatibleProvider` with LM Studio's defaults.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LMStudioProvider {
-    #[serde(skip)] 
-    client: HttpClient,
-    base_url: String,
-    api_key: Option<String>,
-    preferred_model_name: Option<String>,
+    inner: OpenAICompatibleProvider,
 }
 
 impl LMStudioProvider {
     /// Create a new LM Studio provider with the specified base URL
     pub fn new(base_url: &str, api_key: Option<String>) -> Self {
-        let mut headers = header::HeaderMap::new();
-        
-        // Add content-type header
-        headers.insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("application/json"),
-        );
-
-        // Create client with default configuration
-        let client = HttpClient::builder()
-            .default_headers(headers)
-            .build()
-            .expect("Failed to create HTTP client");
+        let config = OpenAICompatibleConfig {
+            name: PROVIDER_ID.to_string(),
+            base_url: base_url.to_string(),
+            api_key,
+            headers: HashMap::new(),
+            query: HashMap::new(),
+            embedding_model: Some("text-embedding-nomic-embed-text-v1.5".to_string()),
+        };
 
         LMStudioProvider {
-            client,
-            base_url: base_url.trim_end_matches('/').to_string(),
-            api_key,
-            preferred_model_name: None,
+            inner: OpenAICompatibleProvider::new(&config).with_provider_id(PROVIDER_ID),
         }
     }
 
-    /// Helper method to add authorization header if API key is set
-    fn add_auth_header(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        match &self.api_key {
-            Some(key) if !key.is_empty() => {
-                builder.header("Authorization", format!("Bearer {}", key))
-            },
-            _ => builder,
+    /// Replace how 429s, 5xx responses and connection failures are retried
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.inner.set_retry_policy(retry_policy);
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.inner.set_connection_settings(connection)
+    }
+
+    /// Every downloaded model from the native API, or None when the server doesn't have it
+    pub async fn native_models(&self) -> Result<Option<Vec<LMStudioModelInfo>>, AIProviderError> {
+        let body = match self.inner.get_native_json("api/v0/models").await {
+            Ok(body) => body,
+            Err(e) if is_missing_endpoint(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        serde_json::from_value(body["data"].clone())
+            .map(Some)
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse LM Studio models: {}", e)))
+    }
+
+    /// Load `model_id` into memory with `context_length` tokens of context, or the model's
+    /// default, and return the id of the loaded instance. Needs LM Studio 0.4 or later.
+    pub async fn load_model(&self, model_id: &str, context_length: Option<usize>) -> Result<String, AIProviderError> {
+        let mut body = json!({ "model": model_id });
+        if let Some(context_length) = context_length {
+            body["context_length"] = json!(context_length);
+        }
+        let response = self.inner.post_native_json("api/v1/models/load", &body).await
+            .map_err(|e| Self::needs_load_api(e, "load"))?;
+        Ok(response["instance_id"].as_str().unwrap_or(model_id).to_string())
+    }
+
+    /// Unload a loaded model, by the instance id `load_model` returned, which for a model
+    /// loaded once is its model id
+    pub async fn unload_model(&self, instance_id: &str) -> Result<(), AIProviderError> {
+        self.inner.post_native_json("api/v1/models/unload", &json!({ "instance_id": instance_id })).await
+            .map_err(|e| Self::needs_load_api(e, "unload"))?;
+        Ok(())
+    }
+
+    fn needs_load_api(error: AIProviderError, action: &str) -> AIProviderError {
+        if is_missing_endpoint(&error) {
+            AIProviderError::NotImplemented(format!("This LM Studio can't {} models over its API; update to 0.4 or later", action))
+        } else {
+            error
         }
     }
 }
@@ -79,127 +157,68 @@ impl LMStudioProvider {
 #[async_trait]
 impl ModelProvider for LMStudioProvider {
     async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
-        let url = format!("{}/models", self.base_url);
-        
-        let request = self.client.get(&url);
-        //let request = self.add_auth_header(request);
-        println!("Request URL: {}", url);
-        println!("Request {:?}", request);
-
-        let response = request.send().await
-            .map_err(|e| AIProviderError::APIError(format!("Network error: {}", e)))?;
-            
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await
-                .unwrap_or_else(|_| "Failed to read response body".to_string());
-            log::debug!("Failed to list models: {}: {}", status, text);  
-            return Err(AIProviderError::APIError(format!(
-                "API returned error {}: {}", status, text
-            )));
+        match self.native_models().await? {
+            Some(models) => Ok(models.iter().map(LMStudioModelInfo::to_ai_model).collect()),
+            None => Ok(self.inner.list_models().await?.into_iter().map(without_guessed_capabilities).collect()),
         }
-        
-        #[derive(Deserialize)]
-        #[derive(Serialize)]
-        struct ModelData {
-            id: String,
-            object: String,
-            created: Option<u64>,
-            owned_by: Option<String>,
-        }
-        
-        #[derive(Deserialize)]
-        struct ModelListResponse {
-            data: Vec<ModelData>,
-            object: String,
-        }
-        
-        let model_list: ModelListResponse = response.json().await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
-            
-        Ok(model_list.data.into_iter()
-            .map(|m| AIModel {
-                id: m.id.clone(),
-                name: m.id.clone(),
-                provider: "lm_studio".to_string(),
-                capabilities: vec![ModelCapability::ChatCompletion], // Most LM Studio models support chat
-                context_length: None, // Not provided by the API
-                additional_info: serde_json::to_value(m).unwrap_or_default(),
-            })
-            .collect())
     }
-    
+
     async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
-        let url = format!("{}/models/{}", self.base_url, model_id);
-        
-        let request = self.client.get(&url);
-        let request = self.add_auth_header(request);
-        println!("Request URL: {}", url);
-        println!("Request {:?}", request);
-
-
-        let response = request.send().await
-            .map_err(|e| AIProviderError::APIError(format!("Network error: {}", e)))?;
-            
-        if !response.status().is_success() {
-            if response.status().as_u16() == 404 {
-                return Err(AIProviderError::ModelNotAvailable(model_id.to_string()));
-            }
-            
-            let status = response.status();
-            let text = response.text().await
-                .unwrap_or_else(|_| "Failed to read response body".to_string());
-                
-            return Err(AIProviderError::APIError(format!(
-                "API returned error {}: {}", status, text
-            )));
+        // The native API answers 404 both for unknown models and when it's missing, so look in the list
+        match self.native_models().await? {
+            Some(models) => models.iter()
+                .find(|model| model.id == model_id)
+                .map(LMStudioModelInfo::to_ai_model)
+                .ok_or_else(|| AIProviderError::ModelNotAvailable(model_id.to_string())),
+            None => self.inner.get_model(model_id).await.map(without_guessed_capabilities),
         }
-        
-        let model_data: Value = response.json().await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
-            
-        Ok(AIModel {
-            id: model_data["id"].as_str().unwrap_or(model_id).to_string(),
-            name: model_data["id"].as_str().unwrap_or(model_id).to_string(),
-            provider: "lm_studio".to_string(),
-            capabilities: vec![ModelCapability::ChatCompletion], // Most LM Studio models support chat
-            context_length: None, // Not provided by the API
-            additional_info: model_data,
-        })
     }
 
     async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
-        // Use the preferred_model_name if set, otherwise use the provided preference_model
-        let model_id = match &self.preferred_model_name {
-            Some(model) => model.clone(),
-            None => preference_model.to_string(),
-        };
-        
-        // Try to fetch the model info
+        let model_id = self.inner.get_preferred_model_name().unwrap_or_else(|| preference_model.to_string());
         match self.get_model(&model_id).await {
             Ok(model) => Ok(model),
-            Err(AIProviderError::ModelNotAvailable(_)) => {
-                // If model isn't available, try to get the first available model
+            Err(e) if e.kind() == AIErrorKind::ModelNotFound => {
+                // Otherwise a chat model, preferably one that's loaded so the answer doesn't wait on a load
                 let models = self.list_models().await?;
-                if let Some(first_model) = models.first() {
-                    Ok(first_model.clone())
-                } else {
-                    Err(AIProviderError::ModelNotAvailable(format!(
-                        "No models available for LM Studio provider"
-                    )))
-                }
-            }
-            Err(err) => Err(err),
+                let is_loaded = |model: &AIModel| model.additional_info["state"] == "loaded";
+                let mut chat_models: Vec<AIModel> = models.into_iter()
+                    .filter(can_chat)
+                    .collect();
+                chat_models.sort_by_key(|model| !is_loaded(model));
+                chat_models.into_iter().next()
+                    .ok_or_else(|| AIProviderError::ModelNotAvailable("No chat models available for LM Studio".to_string()))
+            },
+            Err(e) => Err(e),
         }
     }
 
     fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
-        self.preferred_model_name = Some(model_name);
-        Ok(())
+        self.inner.set_preferred_inference_model(model_name)
     }
 
     fn get_provider_name(&self) -> String {
-        "lm_studio".to_string()
+        PROVIDER_ID.to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.inner.get_preferred_model_name()
+    }
+
+    /// Lists models through the native API when there is one, to also report which are loaded
+    async fn health_check(&self) -> HealthStatus {
+        let started = Instant::now();
+        match self.native_models().await {
+            Ok(Some(models)) => {
+                let loaded_models = models.iter().filter(|model| model.is_loaded()).map(|model| model.id.clone()).collect();
+                let models = models.iter().map(LMStudioModelInfo::to_ai_model).collect();
+                let mut status = HealthStatus::from_models(self.get_provider_name(), started.elapsed(), Ok(models));
+                status.loaded_models = Some(loaded_models);
+                status
+            },
+            Ok(None) => self.inner.health_check().await,
+            Err(e) => HealthStatus::failed(self.get_provider_name(), started.elapsed(), e),
+        }
     }
 }
 
@@ -208,198 +227,15 @@ impl ChatCompletionProvider for LMStudioProvider {
     async fn create_streaming_chat_completion(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send>>, AIProviderError> {
-        // Call the non-streaming completion
-        let completion = self.create_chat_completion(request).await?;
-        completion.choices
-            .iter()
-            .for_each(|choice| {
-                // Log each choice for debugging
-                log::debug!("Choice: {:?}", choice);
-            });
-        // Convert the completion into a single chunk (adapt as needed for your types)
-        let chunk = ChatCompletionChunk {
-            id: completion.id.clone(),
-            choices: completion.choices.iter().map(|choice| ChatCompletionChunkChoice {
-                delta: ChatMessageDelta {
-                                role: None, // Roles typically come in the first chunk only
-                                content: Some(choice.message.content.clone()),
-                            },
-                finish_reason: choice.finish_reason.clone(),
-                index: choice.index,
-            }).collect(),
-            created: completion.created,
-            // ...other fields as needed...
-        };
-
-        // Create a stream that yields this single chunk
-        let stream = stream::once(async move { Ok(chunk) });
-
-        Ok(Box::pin(stream) as Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send>>)
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        self.inner.create_streaming_chat_completion(request).await
     }
 
     async fn create_chat_completion(
-        &self, 
+        &self,
         request: &ChatCompletionRequest
     ) -> Result<ChatCompletionResponse, AIProviderError> {
-        let url = format!("{}/chat/completions", self.base_url);
-        
-        // Serialize messages in OpenAI compatible format
-        #[derive(Serialize, Debug)]
-        struct LMStudioMessage {
-            role: String,
-            content: String,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            name: Option<String>,
-        }
-        
-        #[derive(Serialize, Debug)]
-        struct LMStudioRequest {
-            model: String,
-            messages: Vec<LMStudioMessage>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            temperature: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            max_tokens: Option<u32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            top_p: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            stop: Option<Vec<String>>,
-        }
-        
-        let lm_messages: Vec<LMStudioMessage> = request.messages.iter()
-            .map(|msg| {
-                LMStudioMessage {
-                    role: match msg.role {
-                        MessageRole::System => "system".to_string(),
-                        MessageRole::User => "user".to_string(),
-                        MessageRole::Assistant => "assistant".to_string(),
-                        MessageRole::Tool => "tool".to_string(),
-                        MessageRole::Function => "function".to_string(),
-                    },
-                    content: msg.content.clone(),
-                    name: msg.name.clone(),
-                }
-            })
-            .collect();
-            
-        let lm_request = LMStudioRequest {
-            model: request.model.clone(),
-            messages: lm_messages,
-            temperature: request.temperature,
-            max_tokens: request.max_tokens,
-            top_p: None,
-            stop: None,
-        };
-        
-        // Debug: Print the serialized JSON for inspection
-        let json_string = serde_json::to_string_pretty(&lm_request)
-            .map_err(|e| AIProviderError::APIError(format!("Failed to serialize request: {}", e)))?;
-        
-        log::debug!("LMStudio request JSON: {}", json_string);
-        
-        // Generate equivalent curl command for debugging - no auth header needed for LM Studio
-        let curl_command = format!(
-            "curl -X POST {} -H \"Content-Type: application/json\" --data '{}'",
-            url,
-            json_string.replace("'", "\\'") // Escape single quotes for shell safety
-        );
-        
-        log::debug!("Equivalent curl command: \n{}", curl_command);
-        
-        let http_request = self.client.post(&url).json(&lm_request);
-        let cloned_request = http_request.try_clone()
-            .ok_or_else(|| AIProviderError::APIError("Failed to clone request".to_string()))?;
-        // Add additional debugging for the raw request
-        log::debug!("LMStudio request URL: {}", url);
-        log::debug!("LMStudio request headers: {:?}", cloned_request.build().unwrap().headers());
-        
-        let response = http_request.send().await
-            .map_err(|e| AIProviderError::APIError(format!("Network error: {}", e)))?;
-            
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await
-                .unwrap_or_else(|_| "Failed to read response body".to_string());
-                
-            return Err(AIProviderError::APIError(format!(
-                "API returned error {}: {}", status, text
-            )));
-        }
-        
-        // Parse LM Studio response (OpenAI compatible format)
-        #[derive(Deserialize)]
-        struct LMStudioResponseMessage {
-            role: String,
-            content: String,
-        }
-        
-        #[derive(Deserialize)]
-        struct LMStudioResponseChoice {
-            message: LMStudioResponseMessage,
-            finish_reason: Option<String>,
-            index: usize,
-        }
-        
-        #[derive(Deserialize)]
-        struct LMStudioUsage {
-            prompt_tokens: u32,
-            completion_tokens: u32,
-            total_tokens: u32,
-        }
-        
-        #[derive(Deserialize)]
-        struct LMStudioResponse {
-            id: String,
-            object: String,
-            created: u64,
-            model: String,
-            choices: Vec<LMStudioResponseChoice>,
-            usage: Option<LMStudioUsage>,
-        }
-        
-        let lm_response: LMStudioResponse = response.json().await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
-            
-        // Convert to our generic format
-        let choices = lm_response.choices.iter()
-            .map(|choice| {
-                let role = match choice.message.role.as_str() {
-                    "system" => MessageRole::System,
-                    "user" => MessageRole::User,
-                    "assistant" => MessageRole::Assistant,
-                    "tool" => MessageRole::Tool,
-                    "function" => MessageRole::Function,
-                    _ => MessageRole::Assistant, // Default
-                };
-                
-                ChatCompletionChoice {
-                    message: ChatMessage {
-                        role,
-                        content: choice.message.content.clone(),
-                        name: None,
-                    },
-                    finish_reason: choice.finish_reason.clone(),
-                    index: choice.index,
-                }
-            })
-            .collect();
-            
-        let usage = lm_response.usage.map(|usage| {
-            TokenUsage {
-                prompt_tokens: usage.prompt_tokens,
-                completion_tokens: usage.completion_tokens,
-                total_tokens: usage.total_tokens,
-            }
-        });
-            
-        Ok(ChatCompletionResponse {
-            id: lm_response.id,
-            choices,
-            created: lm_response.created,
-            model: lm_response.model,
-            usage,
-        })
+        self.inner.create_chat_completion(request).await
     }
 }
 
@@ -408,70 +244,40 @@ impl EmbeddingProvider for LMStudioProvider {
     async fn create_embeddings(
         &self,
         embedding_request: EmbeddingRequest,
-        // texts: &[String],
-        // model: &str,
     ) -> Result<Vec<Embedding>, AIProviderError> {
-        let url = format!("{}/embeddings", self.base_url);
-        
-        // LM Studio may not support embeddings, but we'll implement the API call
-        // in case it does in the future or for compatible models
-        let request_body = json!({
-            "model": embedding_request.model,
-            "input": embedding_request.input,
-        });
-        
-        let http_request = self.client.post(&url).json(&request_body);
-        let http_request = self.add_auth_header(http_request);
-        
-        let response = http_request.send().await
-            .map_err(|e| AIProviderError::APIError(format!("Network error: {}", e)))?;
-            
-        if !response.status().is_success() {
-            // Many local LLM servers don't support embeddings
-            if response.status().as_u16() == 404 {
-                return Err(AIProviderError::ModelNotAvailable(
-                    format!("Embeddings not supported by this LM Studio instance")
-                ));
-            }
-            
-            let status = response.status();
-            let text = response.text().await
-                .unwrap_or_else(|_| "Failed to read response body".to_string());
-                
-            return Err(AIProviderError::APIError(format!(
-                "API returned error {}: {}", status, text
-            )));
-        }
-        
-        #[derive(Deserialize)]
-        struct EmbeddingData {
-            embedding: Vec<f32>,
-            index: usize,
-        }
-        
-        #[derive(Deserialize)]
-        struct EmbeddingResponse {
-            data: Vec<EmbeddingData>,
-        }
-        
-        let embedding_response: EmbeddingResponse = response.json().await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
-        let embedding_model_name = PreferredEmbeddingModel::get_preferred_embedding_model(self);
-        Ok(embedding_response.data.into_iter()
-            .map(|e| Embedding {
-                vector: e.embedding,
-                index: e.index,
-                model_name: Some(embedding_model_name.clone()),
-            })
-            .collect())
+        // LM Studio may not support embeddings for every model; the inner provider reports that
+        self.inner.create_embeddings(embedding_request).await
     }
 }
 
 impl PreferredEmbeddingModel for LMStudioProvider {
     fn get_preferred_embedding_model(&self) -> String {
-        "text-embedding-nomic-embed-text-v1.5".to_string()
-        // LM Studio doesn't have a preferred embedding model
-        //unimplemented!("LM Studio does not yet support a preferred embedding model");
+        self.inner.get_preferred_embedding_model()
     }
-    
-}
\ No newline at end of file
+}
+
+impl AIProvider for LMStudioProvider {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::mock_provider::test_support::request;
+    use crate::ai::providers::test_server::serve_once;
+
+    #[tokio::test]
+    async fn errors_name_the_provider_like_its_models_do() {
+        let (base_url, server) = serve_once(
+            "400 Bad Request",
+            "application/json",
+            r#"{"error": {"message": "Model unloaded.", "type": "invalid_request_error"}}"#,
+        ).await;
+        let mut provider = LMStudioProvider::new(&base_url, None);
+        provider.set_retry_policy(RetryPolicy::none());
+
+        let error = provider.create_chat_completion(&request("qwen3-8b", "Hello")).await.unwrap_err();
+        server.await.unwrap();
+
+        assert_eq!(error.details().unwrap().provider, provider.get_provider_name());
+        assert!(error.to_string().starts_with("lm_studio: Model unloaded."), "{}", error);
+    }
+}
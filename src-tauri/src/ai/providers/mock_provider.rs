@@ -0,0 +1,492 @@
+use crate::ai::traits::{ChatCompletionProvider, DiffusingProvider, ModelProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, ProviderErrorDetails, ChatCompletionStream};
+use crate::ai::models::*;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Model id served when the fixtures don't list any models
+pub const MOCK_DEFAULT_MODEL: &str = "mock-model";
+
+/// An error a fixture can script, mirroring `AIProviderError`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum MockError {
+    APIError(String),
+    RateLimitExceeded,
+    AuthError(String),
+    ModelNotAvailable(String),
+    InvalidRequest(String),
+    Other(String),
+    NotImplemented(String),
+    ModelNotFound(String),
+    DeserializationError(String),
+    Cancelled,
+    /// A classified provider error, e.g. `{kind: Provider, message: {kind: ContextLengthExceeded, provider: Mock, message: ...}}`
+    Provider(ProviderErrorDetails),
+}
+
+impl From<MockError> for AIProviderError {
+    fn from(error: MockError) -> Self {
+        match error {
+            MockError::APIError(message) => AIProviderError::APIError(message),
+            MockError::RateLimitExceeded => AIProviderError::RateLimitExceeded,
+            MockError::AuthError(message) => AIProviderError::AuthError(message),
+            MockError::ModelNotAvailable(message) => AIProviderError::ModelNotAvailable(message),
+            MockError::InvalidRequest(message) => AIProviderError::InvalidRequest(message),
+            MockError::Other(message) => AIProviderError::Other(message),
+            MockError::NotImplemented(message) => AIProviderError::NotImplemented(message),
+            MockError::ModelNotFound(message) => AIProviderError::ModelNotFound(message),
+            MockError::DeserializationError(message) => AIProviderError::DeserializationError(message),
+            MockError::Cancelled => AIProviderError::Cancelled,
+            MockError::Provider(details) => AIProviderError::Provider(Box::new(details)),
+        }
+    }
+}
+
+impl From<&AIProviderError> for MockError {
+    fn from(error: &AIProviderError) -> Self {
+        match error {
+            AIProviderError::APIError(message) => MockError::APIError(message.clone()),
+            AIProviderError::RateLimitExceeded => MockError::RateLimitExceeded,
+            AIProviderError::AuthError(message) => MockError::AuthError(message.clone()),
+            AIProviderError::ModelNotAvailable(message) => MockError::ModelNotAvailable(message.clone()),
+            AIProviderError::InvalidRequest(message) => MockError::InvalidRequest(message.clone()),
+            AIProviderError::Other(message) => MockError::Other(message.clone()),
+            AIProviderError::NotImplemented(message) => MockError::NotImplemented(message.clone()),
+            AIProviderError::ModelNotFound(message) => MockError::ModelNotFound(message.clone()),
+            AIProviderError::DeserializationError(message) => MockError::DeserializationError(message.clone()),
+            AIProviderError::Cancelled => MockError::Cancelled,
+            AIProviderError::Provider(details) => MockError::Provider((**details).clone()),
+        }
+    }
+}
+
+/// A model the mock provider reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MockModel {
+    pub id: String,
+    #[serde(default = "default_mock_capabilities")]
+    pub capabilities: Vec<ModelCapability>,
+    #[serde(default)]
+    pub context_length: Option<usize>,
+}
+
+fn default_mock_capabilities() -> Vec<ModelCapability> {
+    vec![ModelCapability::ChatCompletion, ModelCapability::Embedding]
+}
+
+/// A canned answer. Every matcher that is set must match; a fixture with no matchers matches
+/// any request. The first matching fixture wins.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockFixture {
+    /// Exact text of the last user message
+    pub prompt: Option<String>,
+    /// Substring of the last user message
+    pub prompt_contains: Option<String>,
+    /// Substring of the system prompt, i.e. which transform preset sent the request
+    pub preset: Option<String>,
+    /// Only match requests for this model
+    pub model: Option<String>,
+    /// Text to answer with
+    pub response: Option<String>,
+    /// Error to fail with instead of answering
+    pub error: Option<MockError>,
+    /// When streaming, send this many chunks before failing with `error`
+    pub fail_after_chunks: Option<usize>,
+    /// Stop matching after this many uses, so e.g. a rate limit can clear on retry
+    pub times: Option<u32>,
+    /// Overrides `MockFixtures::latency_ms` for this fixture
+    pub latency_ms: Option<u64>,
+}
+
+/// The contents of a fixtures file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockFixtures {
+    pub models: Vec<MockModel>,
+    /// Answer for requests no fixture matches; `None` echoes the prompt back
+    pub default_response: Option<String>,
+    pub responses: Vec<MockFixture>,
+    /// Delay before answering (or before the first streamed chunk)
+    pub latency_ms: u64,
+    /// Characters per streamed chunk
+    pub chunk_size: usize,
+    /// Delay between streamed chunks
+    pub chunk_delay_ms: u64,
+    pub embedding_dimensions: usize,
+}
+
+impl Default for MockFixtures {
+    fn default() -> Self {
+        MockFixtures {
+            models: Vec::new(),
+            default_response: None,
+            responses: Vec::new(),
+            latency_ms: 0,
+            chunk_size: 8,
+            chunk_delay_ms: 0,
+            embedding_dimensions: 8,
+        }
+    }
+}
+
+impl MockFixtures {
+    /// Parse fixtures from YAML
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, AIProviderError> {
+        serde_yaml::from_str(yaml)
+            .map_err(|e| AIProviderError::DeserializationError(format!("Invalid mock fixtures: {}", e)))
+    }
+
+    /// Load fixtures from a YAML file
+    pub fn from_yaml_file(path: impl AsRef<Path>) -> Result<Self, AIProviderError> {
+        let path = path.as_ref();
+        let yaml = std::fs::read_to_string(path)
+            .map_err(|e| AIProviderError::Other(format!("Failed to read mock fixtures {}: {}", path.display(), e)))?;
+        Self::from_yaml_str(&yaml)
+    }
+}
+
+/// What a request resolved to after matching it against the fixtures
+struct MockOutcome {
+    content: String,
+    error: Option<MockError>,
+    fail_after_chunks: Option<usize>,
+    latency: Duration,
+}
+
+/// Deterministic provider that answers from fixtures, for working without keys or model servers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MockProvider {
+    fixtures: MockFixtures,
+    /// How many times each fixture has been used, shared between clones
+    #[serde(skip)]
+    uses: Arc<Mutex<Vec<u32>>>,
+    preferred_model_name: Option<String>,
+}
+
+impl MockProvider {
+    /// Create a mock provider serving the given fixtures
+    pub fn new(fixtures: MockFixtures) -> Self {
+        let uses = vec![0; fixtures.responses.len()];
+        MockProvider {
+            fixtures,
+            uses: Arc::new(Mutex::new(uses)),
+            preferred_model_name: None,
+        }
+    }
+
+    fn models(&self) -> Vec<MockModel> {
+        if self.fixtures.models.is_empty() {
+            vec![MockModel {
+                id: MOCK_DEFAULT_MODEL.to_string(),
+                capabilities: default_mock_capabilities(),
+                context_length: Some(8192),
+            }]
+        } else {
+            self.fixtures.models.clone()
+        }
+    }
+
+    fn to_ai_model(model: &MockModel) -> AIModel {
+        AIModel {
+            id: model.id.clone(),
+            name: model.id.clone(),
+            provider: "mock".to_string(),
+            capabilities: model.capabilities.clone(),
+            context_length: model.context_length,
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: serde_json::json!({}),
+        }
+    }
+
+    /// Find the first fixture matching the request and count the use
+    fn resolve(&self, request: &ChatCompletionRequest) -> MockOutcome {
+        let prompt = request.messages.iter()
+            .rev()
+            .find(|m| m.role == MessageRole::User)
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+        let system_prompt = request.messages.iter()
+            .filter(|m| m.role == MessageRole::System)
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut uses = self.uses.lock().unwrap();
+        let matched = self.fixtures.responses.iter().enumerate().find(|(index, fixture)| {
+            fixture.times.is_none_or(|times| uses[*index] < times)
+                && fixture.prompt.as_deref().is_none_or(|p| p == prompt)
+                && fixture.prompt_contains.as_deref().is_none_or(|p| prompt.contains(p))
+                && fixture.preset.as_deref().is_none_or(|p| system_prompt.contains(p))
+                && fixture.model.as_deref().is_none_or(|m| m == request.model)
+        });
+
+        let default_latency = Duration::from_millis(self.fixtures.latency_ms);
+        let default_content = || self.fixtures.default_response.clone()
+            .unwrap_or_else(|| prompt.to_string());
+
+        match matched {
+            Some((index, fixture)) => {
+                uses[index] += 1;
+                MockOutcome {
+                    content: fixture.response.clone().unwrap_or_else(default_content),
+                    error: fixture.error.clone(),
+                    fail_after_chunks: fixture.fail_after_chunks,
+                    latency: fixture.latency_ms.map(Duration::from_millis).unwrap_or(default_latency),
+                }
+            },
+            None => MockOutcome {
+                content: default_content(),
+                error: None,
+                fail_after_chunks: None,
+                latency: default_latency,
+            },
+        }
+    }
+
+    fn completion_id(request: &ChatCompletionRequest) -> String {
+        let text: String = request.messages.iter().map(|m| m.content.as_str()).collect();
+        format!("mockcmpl-{:016x}", fnv1a(text.as_bytes()))
+    }
+
+    fn usage(request: &ChatCompletionRequest, content: &str) -> TokenUsage {
+        // Whitespace separated words stand in for tokens
+        let prompt_tokens = request.messages.iter()
+            .map(|m| m.content.split_whitespace().count() as u32)
+            .sum();
+        let completion_tokens = content.split_whitespace().count() as u32;
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    /// Stream the resolved answer in chunks. `cumulative` chunks carry the whole text so far.
+    fn open_stream(
+        &self,
+        request: &ChatCompletionRequest,
+        cumulative: bool,
+    ) -> impl Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send {
+        let outcome = self.resolve(request);
+
+        let id = Self::completion_id(request);
+        let created = chrono::Utc::now().timestamp() as u64;
+        let chars: Vec<char> = outcome.content.chars().collect();
+        let pieces: Vec<String> = chars.chunks(self.fixtures.chunk_size.max(1))
+            .map(|piece| piece.iter().collect())
+            .collect();
+        let piece_count = pieces.len();
+        let usage = Self::usage(request, &outcome.content);
+
+        let mut so_far = String::new();
+        let mut items: Vec<Result<ChatCompletionChunk, AIProviderError>> = Vec::new();
+        // Without `fail_after_chunks` a scripted error fails the stream before any content
+        let fail_after_chunks = match outcome.error {
+            Some(_) => outcome.fail_after_chunks.unwrap_or(0),
+            None => usize::MAX,
+        };
+        for (index, piece) in pieces.into_iter().enumerate() {
+            if index == fail_after_chunks {
+                break;
+            }
+            so_far.push_str(&piece);
+            let is_last = index + 1 == piece_count;
+            items.push(Ok(ChatCompletionChunk {
+                id: id.clone(),
+                choices: vec![ChatCompletionChunkChoice {
+                    delta: ChatMessageDelta {
+                        role: if index == 0 { Some(MessageRole::Assistant) } else { None },
+                        content: Some(if cumulative { so_far.clone() } else { piece }),
+                    },
+                    finish_reason: if is_last { Some("stop".to_string()) } else { None },
+                    index: 0,
+                }],
+                created,
+                usage: if is_last { Some(usage.clone()) } else { None },
+            }));
+        }
+        if let Some(error) = outcome.error {
+            items.push(Err(error.into()));
+        }
+
+        let first_delay = outcome.latency;
+        let chunk_delay = Duration::from_millis(self.fixtures.chunk_delay_ms);
+        futures::stream::iter(items.into_iter().enumerate())
+            .then(move |(index, item)| async move {
+                tokio::time::sleep(if index == 0 { first_delay } else { chunk_delay }).await;
+                item
+            })
+    }
+}
+
+/// FNV-1a, so mock ids, embeddings and cassette keys are stable across runs and Rust versions
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[async_trait]
+impl ModelProvider for MockProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        Ok(self.models().iter().map(Self::to_ai_model).collect())
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        self.models().iter()
+            .find(|m| m.id == model_id)
+            .map(Self::to_ai_model)
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(model_id.to_string()))
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        if let Ok(model) = self.get_model(&model_id).await {
+            return Ok(model);
+        }
+
+        self.models().iter()
+            .find(|m| m.capabilities.contains(&ModelCapability::ChatCompletion))
+            .map(Self::to_ai_model)
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(
+                "No chat models in the mock fixtures".to_string()
+            ))
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        "mock".to_string()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for MockProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let outcome = self.resolve(request);
+        tokio::time::sleep(outcome.latency).await;
+
+        if let Some(error) = outcome.error {
+            return Err(error.into());
+        }
+
+        Ok(ChatCompletionResponse {
+            id: Self::completion_id(request),
+            choices: vec![ChatCompletionChoice {
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: outcome.content.clone(),
+                    name: None,
+                },
+                finish_reason: Some("stop".to_string()),
+                index: 0,
+            }],
+            created: chrono::Utc::now().timestamp() as u64,
+            model: request.model.clone(),
+            usage: Some(Self::usage(request, &outcome.content)),
+        })
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        Ok(Box::pin(self.open_stream(request, false)))
+    }
+}
+
+#[async_trait]
+impl DiffusingProvider for MockProvider {
+    async fn create_diffusing_stream(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        Ok(Box::pin(self.open_stream(request, true)))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for MockProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        let dimensions = self.fixtures.embedding_dimensions.max(1);
+
+        // Seed each dimension from the text's hash so equal inputs get equal vectors
+        Ok(embedding_request.input.iter().enumerate()
+            .map(|(index, text)| {
+                let seed = fnv1a(text.as_bytes());
+                let vector = (0..dimensions)
+                    .map(|d| {
+                        let h = fnv1a(&(seed ^ d as u64).to_le_bytes());
+                        (h % 2001) as f32 / 1000.0 - 1.0
+                    })
+                    .collect();
+                Embedding {
+                    vector,
+                    index,
+                    model_name: Some(embedding_request.model.clone()),
+                }
+            })
+            .collect())
+    }
+}
+
+impl PreferredEmbeddingModel for MockProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        self.models().iter()
+            .find(|m| m.capabilities.contains(&ModelCapability::Embedding))
+            .map(|m| m.id.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl AIProvider for MockProvider {
+    fn as_diffusing(&self) -> Option<&dyn DiffusingProvider> {
+        Some(self)
+    }
+}
+
+/// Shorthands for tests that script a provider with fixtures
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::{MockFixtures, MockProvider};
+    use crate::ai::models::{ChatCompletionRequest, ChatMessage, MessageRole};
+    use crate::ai::providers::Provider;
+
+    /// A mock provider scripted by the YAML `fixtures`
+    pub(crate) fn mock(fixtures: &str) -> Provider {
+        Provider::Mock(MockProvider::new(MockFixtures::from_yaml_str(fixtures).unwrap()))
+    }
+
+    /// A single user message `prompt` for `model`
+    pub(crate) fn request(model: &str, prompt: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: model.to_string(),
+            messages: vec![ChatMessage { role: MessageRole::User, content: prompt.to_string(), name: None }],
+            ..Default::default()
+        }
+    }
+}
@@ -2,16 +2,45 @@
 pub mod openai_provider;
 pub mod lm_studio_provider;
 pub mod ollama_provider;
+pub mod inception_labs_provider;
+pub mod anthropic_provider;
+pub mod gemini_provider;
+pub mod openai_compatible_provider;
+pub mod azure_openai_provider;
+pub mod mock_provider;
+pub mod cassette_provider;
+pub mod registry;
+pub mod fallback_provider;
+pub mod retry;
+pub mod connection;
+pub mod discovery;
+mod sse;
+mod http_error;
+#[cfg(test)]
+mod test_server;
 
 // Re-export the provider structs so they can be used directly from ai::providers
 pub use openai_provider::OpenAIProvider;
-pub use lm_studio_provider::LMStudioProvider;
-pub use ollama_provider::OllamaProvider;
+pub use lm_studio_provider::{LMStudioProvider, LMStudioModelInfo};
+pub use ollama_provider::{OllamaProvider, OllamaOptions, OllamaModelDetails, OllamaPullProgress, OllamaPullStream, OllamaDiskUsage, OllamaModelSize, KeepAlive};
+pub use inception_labs_provider::InceptionLabsProvider;
+pub use anthropic_provider::AnthropicProvider;
+pub use gemini_provider::GeminiProvider;
+pub use openai_compatible_provider::{OpenAICompatibleProvider, OpenAICompatibleConfig};
+pub use azure_openai_provider::{AzureOpenAIProvider, AzureOpenAIConfig};
+pub use mock_provider::{MockProvider, MockFixtures};
+pub use cassette_provider::{CassetteProvider, CassetteMode};
+pub use fallback_provider::{FallbackProvider, FallbackLink, FallbackLinkConfig, FallbackResponse, FallbackAttempt};
+pub use retry::{RetryPolicy, RetryEvent};
+pub use connection::{ConnectionSettings, ConnectionConfig};
+pub use discovery::{LocalServerKind, DiscoveredServer, discover_local_servers, probe_local_server};
+pub use registry::{ProviderRegistry, ProviderDescriptor, ProviderFactory, ConfigField, ConfigFieldKind};
 
 use crate::ai::{
-    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProviderError},
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, DiffusingProvider, AIProvider, AIProviderError, ChatCompletionStream, HealthStatus},
     models::*
 };
+use crate::ai::catalog::ModelCatalog;
 use std::sync::Arc;
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
@@ -21,6 +50,15 @@ pub enum ProviderType {
     OpenAI,
     LMStudio,
     Ollama,
+    InceptionLabs,
+    Anthropic,
+    Gemini,
+    /// A saved OpenAI compatible endpoint; carries its own configuration
+    OpenAICompatible(OpenAICompatibleConfig),
+    /// An Azure OpenAI resource; carries its endpoint and deployment table
+    AzureOpenAI(AzureOpenAIConfig),
+    /// Deterministic offline provider; carries its fixtures
+    Mock(MockFixtures),
 }
 
 /// Enum to wrap different provider implementations
@@ -29,6 +67,58 @@ pub enum Provider {
     OpenAI(OpenAIProvider),
     LMStudio(LMStudioProvider),
     Ollama(OllamaProvider),
+    InceptionLabs(InceptionLabsProvider),
+    Anthropic(AnthropicProvider),
+    Gemini(GeminiProvider),
+    OpenAICompatible(OpenAICompatibleProvider),
+    AzureOpenAI(AzureOpenAIProvider),
+    Mock(MockProvider),
+    /// Records or replays the exchanges of the provider it wraps
+    Cassette(CassetteProvider),
+    /// A provider from outside this crate
+    Custom(Arc<dyn AIProvider>),
+}
+
+impl Provider {
+    /// Fill in a model from the model catalog. Mock models are exactly what the fixtures say,
+    /// cassettes wrap a provider that already did this, and Ollama and LM Studio report
+    /// capabilities themselves.
+    fn apply_catalog(&self, model: &mut AIModel) {
+        match self {
+            Provider::Mock(_) | Provider::Cassette(_) => {},
+            Provider::Ollama(_) | Provider::LMStudio(_) => ModelCatalog::global().apply_to_reported(model),
+            _ => ModelCatalog::global().apply(model),
+        }
+    }
+
+    /// Replace the retry policy of providers that retry on their own. Providers that don't
+    /// retry yet ignore it.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        match self {
+            Provider::OpenAI(provider) => provider.set_retry_policy(retry_policy),
+            Provider::LMStudio(provider) => provider.set_retry_policy(retry_policy),
+            Provider::Ollama(provider) => provider.set_retry_policy(retry_policy),
+            Provider::OpenAICompatible(provider) => provider.set_retry_policy(retry_policy),
+            Provider::AzureOpenAI(provider) => provider.set_retry_policy(retry_policy),
+            _ => {},
+        }
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent to providers that talk HTTP. The mock,
+    /// cassettes and custom providers are left alone.
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        match self {
+            Provider::OpenAI(provider) => provider.set_connection_settings(connection),
+            Provider::LMStudio(provider) => provider.set_connection_settings(connection),
+            Provider::Ollama(provider) => provider.set_connection_settings(connection),
+            Provider::InceptionLabs(provider) => provider.set_connection_settings(connection),
+            Provider::Anthropic(provider) => provider.set_connection_settings(connection),
+            Provider::Gemini(provider) => provider.set_connection_settings(connection),
+            Provider::OpenAICompatible(provider) => provider.set_connection_settings(connection),
+            Provider::AzureOpenAI(provider) => provider.set_connection_settings(connection),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl Serialize for Provider {
@@ -40,6 +130,14 @@ impl Serialize for Provider {
             Provider::OpenAI(provider) => provider.serialize(serializer),
             Provider::LMStudio(provider) => provider.serialize(serializer),
             Provider::Ollama(provider) => provider.serialize(serializer),
+            Provider::InceptionLabs(provider) => provider.serialize(serializer),
+            Provider::Anthropic(provider) => provider.serialize(serializer),
+            Provider::Gemini(provider) => provider.serialize(serializer),
+            Provider::OpenAICompatible(provider) => provider.serialize(serializer),
+            Provider::AzureOpenAI(provider) => provider.serialize(serializer),
+            Provider::Mock(provider) => provider.serialize(serializer),
+            Provider::Cassette(provider) => provider.serialize(serializer),
+            Provider::Custom(provider) => serde_json::json!({ "provider": provider.get_provider_name() }).serialize(serializer),
         }
     }
 }
@@ -78,6 +176,26 @@ pub fn create_provider(provider_type: ProviderType, config: &str) -> Provider {
         ProviderType::Ollama => {
             Provider::Ollama(OllamaProvider::new(config))
         },
+        ProviderType::InceptionLabs => {
+            // Config is the API key; the public endpoint is used
+            Provider::InceptionLabs(InceptionLabsProvider::new(config.to_string(), String::new()))
+        },
+        ProviderType::Anthropic => {
+            Provider::Anthropic(AnthropicProvider::new(config))
+        },
+        ProviderType::Gemini => {
+            Provider::Gemini(GeminiProvider::new(config))
+        },
+        ProviderType::OpenAICompatible(endpoint) => {
+            // The endpoint carries its own URL and key, so `config` isn't needed
+            Provider::OpenAICompatible(OpenAICompatibleProvider::new(&endpoint))
+        },
+        ProviderType::AzureOpenAI(azure_config) => {
+            Provider::AzureOpenAI(AzureOpenAIProvider::new(&azure_config))
+        },
+        ProviderType::Mock(fixtures) => {
+            Provider::Mock(MockProvider::new(fixtures))
+        },
     }
 }
 
@@ -91,17 +209,56 @@ impl ChatCompletionProvider for Provider {
             Provider::OpenAI(provider) => provider.create_chat_completion(request).await,
             Provider::LMStudio(provider) => provider.create_chat_completion(request).await,
             Provider::Ollama(provider) => provider.create_chat_completion(request).await,
+            Provider::InceptionLabs(provider) => provider.create_chat_completion(request).await,
+            Provider::Anthropic(provider) => provider.create_chat_completion(request).await,
+            Provider::Gemini(provider) => provider.create_chat_completion(request).await,
+            Provider::OpenAICompatible(provider) => provider.create_chat_completion(request).await,
+            Provider::AzureOpenAI(provider) => provider.create_chat_completion(request).await,
+            Provider::Mock(provider) => provider.create_chat_completion(request).await,
+            Provider::Cassette(provider) => provider.create_chat_completion(request).await,
+            Provider::Custom(provider) => provider.create_chat_completion(request).await,
         }
     }
 
     async fn create_streaming_chat_completion(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<impl futures::Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send, AIProviderError> {
+    ) -> Result<ChatCompletionStream, AIProviderError> {
         match self {
             Provider::OpenAI(provider) => provider.create_streaming_chat_completion(request).await,
             Provider::LMStudio(provider) => provider.create_streaming_chat_completion(request).await,
             Provider::Ollama(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::InceptionLabs(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::Anthropic(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::Gemini(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::OpenAICompatible(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::AzureOpenAI(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::Mock(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::Cassette(provider) => provider.create_streaming_chat_completion(request).await,
+            Provider::Custom(provider) => provider.create_streaming_chat_completion(request).await,
+        }
+    }
+}
+
+#[async_trait]
+impl DiffusingProvider for Provider {
+    async fn create_diffusing_stream(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        match self {
+            Provider::InceptionLabs(provider) => provider.create_diffusing_stream(request).await,
+            Provider::Mock(provider) => provider.create_diffusing_stream(request).await,
+            Provider::Cassette(provider) => provider.create_diffusing_stream(request).await,
+            Provider::Custom(provider) => match provider.as_diffusing() {
+                Some(diffusing) => diffusing.create_diffusing_stream(request).await,
+                None => Err(AIProviderError::NotImplemented(format!(
+                    "Diffusing streams are not supported by {}", provider.get_provider_name()
+                ))),
+            },
+            _ => Err(AIProviderError::NotImplemented(format!(
+                "Diffusing streams are not supported by {}", self.get_provider_name()
+            ))),
         }
     }
 }
@@ -116,6 +273,14 @@ impl EmbeddingProvider for Provider {
             Provider::OpenAI(provider) => provider.create_embeddings(embedding_request).await,
             Provider::LMStudio(provider) => provider.create_embeddings(embedding_request).await,
             Provider::Ollama(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::InceptionLabs(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::Anthropic(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::Gemini(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::OpenAICompatible(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::AzureOpenAI(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::Mock(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::Cassette(provider) => provider.create_embeddings(embedding_request).await,
+            Provider::Custom(provider) => provider.create_embeddings(embedding_request).await,
         }
     }
 }
@@ -123,27 +288,59 @@ impl EmbeddingProvider for Provider {
 #[async_trait]
 impl ModelProvider for Provider {
     async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
-        match self {
+        let mut models = match self {
             Provider::OpenAI(provider) => provider.list_models().await,
             Provider::LMStudio(provider) => provider.list_models().await,
             Provider::Ollama(provider) => provider.list_models().await,
+            Provider::InceptionLabs(provider) => provider.list_models().await,
+            Provider::Anthropic(provider) => provider.list_models().await,
+            Provider::Gemini(provider) => provider.list_models().await,
+            Provider::OpenAICompatible(provider) => provider.list_models().await,
+            Provider::AzureOpenAI(provider) => provider.list_models().await,
+            Provider::Mock(provider) => provider.list_models().await,
+            Provider::Cassette(provider) => provider.list_models().await,
+            Provider::Custom(provider) => provider.list_models().await,
+        }?;
+        for model in &mut models {
+            self.apply_catalog(model);
         }
+        Ok(models)
     }
     
     async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
-        match self {
+        let mut model = match self {
             Provider::OpenAI(provider) => provider.get_model(model_id).await,
             Provider::LMStudio(provider) => provider.get_model(model_id).await,
             Provider::Ollama(provider) => provider.get_model(model_id).await,
-        }
+            Provider::InceptionLabs(provider) => provider.get_model(model_id).await,
+            Provider::Anthropic(provider) => provider.get_model(model_id).await,
+            Provider::Gemini(provider) => provider.get_model(model_id).await,
+            Provider::OpenAICompatible(provider) => provider.get_model(model_id).await,
+            Provider::AzureOpenAI(provider) => provider.get_model(model_id).await,
+            Provider::Mock(provider) => provider.get_model(model_id).await,
+            Provider::Cassette(provider) => provider.get_model(model_id).await,
+            Provider::Custom(provider) => provider.get_model(model_id).await,
+        }?;
+        self.apply_catalog(&mut model);
+        Ok(model)
     }
 
     async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
-        match self {
+        let mut model = match self {
             Provider::OpenAI(provider) => provider.get_preferred_inference_model(preference_model).await,
             Provider::LMStudio(provider) => provider.get_preferred_inference_model(preference_model).await,
             Provider::Ollama(provider) => provider.get_preferred_inference_model(preference_model).await,
-        }
+            Provider::InceptionLabs(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::Anthropic(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::Gemini(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::OpenAICompatible(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::AzureOpenAI(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::Mock(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::Cassette(provider) => provider.get_preferred_inference_model(preference_model).await,
+            Provider::Custom(provider) => provider.get_preferred_inference_model(preference_model).await,
+        }?;
+        self.apply_catalog(&mut model);
+        Ok(model)
     }
 
     fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
@@ -151,6 +348,17 @@ impl ModelProvider for Provider {
             Provider::OpenAI(provider) => provider.set_preferred_inference_model(model_name),
             Provider::LMStudio(provider) => provider.set_preferred_inference_model(model_name),
             Provider::Ollama(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::InceptionLabs(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::Anthropic(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::Gemini(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::OpenAICompatible(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::AzureOpenAI(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::Mock(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::Cassette(provider) => provider.set_preferred_inference_model(model_name),
+            Provider::Custom(provider) => match Arc::get_mut(provider) {
+                Some(provider) => provider.set_preferred_inference_model(model_name),
+                None => Err(AIProviderError::Other("Can't change the preferred model of a shared provider".to_string())),
+            },
         }
     }
 
@@ -159,6 +367,57 @@ impl ModelProvider for Provider {
             Provider::OpenAI(provider) => provider.get_provider_name(),
             Provider::LMStudio(provider) => provider.get_provider_name(),
             Provider::Ollama(provider) => provider.get_provider_name(),
+            Provider::InceptionLabs(provider) => provider.get_provider_name(),
+            Provider::Anthropic(provider) => provider.get_provider_name(),
+            Provider::Gemini(provider) => provider.get_provider_name(),
+            Provider::OpenAICompatible(provider) => provider.get_provider_name(),
+            Provider::AzureOpenAI(provider) => provider.get_provider_name(),
+            Provider::Mock(provider) => provider.get_provider_name(),
+            Provider::Cassette(provider) => provider.get_provider_name(),
+            Provider::Custom(provider) => provider.get_provider_name(),
+        }
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        match self {
+            Provider::OpenAI(provider) => provider.get_preferred_model_name(),
+            Provider::LMStudio(provider) => provider.get_preferred_model_name(),
+            Provider::Ollama(provider) => provider.get_preferred_model_name(),
+            Provider::InceptionLabs(provider) => provider.get_preferred_model_name(),
+            Provider::Anthropic(provider) => provider.get_preferred_model_name(),
+            Provider::Gemini(provider) => provider.get_preferred_model_name(),
+            Provider::OpenAICompatible(provider) => provider.get_preferred_model_name(),
+            Provider::AzureOpenAI(provider) => provider.get_preferred_model_name(),
+            Provider::Mock(provider) => provider.get_preferred_model_name(),
+            Provider::Cassette(provider) => provider.get_preferred_model_name(),
+            Provider::Custom(provider) => provider.get_preferred_model_name(),
+        }
+    }
+
+    async fn health_check(&self) -> HealthStatus {
+        match self {
+            Provider::OpenAI(provider) => provider.health_check().await,
+            Provider::LMStudio(provider) => provider.health_check().await,
+            Provider::Ollama(provider) => provider.health_check().await,
+            Provider::InceptionLabs(provider) => provider.health_check().await,
+            Provider::Anthropic(provider) => provider.health_check().await,
+            Provider::Gemini(provider) => provider.health_check().await,
+            Provider::OpenAICompatible(provider) => provider.health_check().await,
+            Provider::AzureOpenAI(provider) => provider.health_check().await,
+            Provider::Mock(provider) => provider.health_check().await,
+            Provider::Cassette(provider) => provider.health_check().await,
+            Provider::Custom(provider) => provider.health_check().await,
+        }
+    }
+}
+
+impl AIProvider for Provider {
+    fn as_diffusing(&self) -> Option<&dyn DiffusingProvider> {
+        match self {
+            Provider::InceptionLabs(_) | Provider::Mock(_) => Some(self),
+            Provider::Cassette(provider) => provider.as_diffusing(),
+            Provider::Custom(provider) => provider.as_diffusing(),
+            _ => None,
         }
     }
 }
@@ -169,6 +428,14 @@ impl PreferredEmbeddingModel for Provider {
             Provider::OpenAI(provider) => provider.get_preferred_embedding_model(),
             Provider::LMStudio(provider) => provider.get_preferred_embedding_model(),
             Provider::Ollama(provider) => provider.get_preferred_embedding_model(),
+            Provider::InceptionLabs(provider) => provider.get_preferred_embedding_model(),
+            Provider::Anthropic(provider) => provider.get_preferred_embedding_model(),
+            Provider::Gemini(provider) => provider.get_preferred_embedding_model(),
+            Provider::OpenAICompatible(provider) => provider.get_preferred_embedding_model(),
+            Provider::AzureOpenAI(provider) => provider.get_preferred_embedding_model(),
+            Provider::Mock(provider) => provider.get_preferred_embedding_model(),
+            Provider::Cassette(provider) => provider.get_preferred_embedding_model(),
+            Provider::Custom(provider) => provider.get_preferred_embedding_model(),
         }
     }
 }
\ No newline at end of file
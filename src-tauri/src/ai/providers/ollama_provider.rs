@@ -1,31 +1,329 @@
 use crate::ai::{
-    models::*, traits::{AIProviderError, ChatCompletionProvider, EmbeddingProvider, ModelProvider, PreferredEmbeddingModel}
+    models::*, traits::{AIProvider, AIProviderError, ChatCompletionProvider, EmbeddingProvider, ModelProvider, PreferredEmbeddingModel, ChatCompletionStream, HealthStatus},
+    resolver::can_chat,
 };
 
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
 use futures::{Stream, StreamExt};
-use std::pin::Pin;
 use ollama_rs::{
     generation::{
-        chat::{request::ChatMessageRequest, ChatMessage}, 
         embeddings::request::EmbeddingsInput,
         embeddings::request::GenerateEmbeddingsRequest,
     },
     Ollama,
-    generation::completion::request::GenerationRequest,
+    error::OllamaError,
+    models::LocalModel,
     generation::options::GenerationOptions,
 };
 use uuid::Uuid;
+use reqwest::{Client as HttpClient, header::HeaderMap};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::time::Instant;
+use super::sse::json_lines_stream;
+use super::connection::ConnectionSettings;
+use super::retry::RetryPolicy;
+use super::http_error::{check_response, classify, details_from_body, network_error};
 
 use tokio::io::{stdout, AsyncWriteExt};
 
+/// How long Ollama keeps a model loaded after a request, either seconds or a duration like "10m"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeepAlive {
+    Seconds(i64),
+    Duration(String),
+}
+
+/// Ollama generation settings, usable as a saved per-model profile or as per-request overrides.
+/// Anything left unset falls back to the model's own Modelfile defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OllamaOptions {
+    pub num_ctx: Option<u64>,
+    pub num_predict: Option<i32>,
+    pub temperature: Option<f32>,
+    pub top_k: Option<u32>,
+    pub top_p: Option<f32>,
+    pub tfs_z: Option<f32>,
+    pub repeat_penalty: Option<f32>,
+    pub repeat_last_n: Option<i32>,
+    pub seed: Option<i32>,
+    pub stop: Option<Vec<String>>,
+    pub mirostat: Option<u8>,
+    pub mirostat_eta: Option<f32>,
+    pub mirostat_tau: Option<f32>,
+    pub num_gqa: Option<u32>,
+    pub num_gpu: Option<u32>,
+    pub num_thread: Option<u32>,
+    pub keep_alive: Option<KeepAlive>,
+}
+
+impl OllamaOptions {
+    /// The options a generic chat request asks for
+    pub fn from_request(request: &ChatCompletionRequest) -> Self {
+        OllamaOptions {
+            num_predict: request.max_tokens.map(|max_tokens| max_tokens as i32),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            seed: request.seed.map(|seed| seed as i32),
+            stop: request.stop.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Layer `overrides` on top of these options; any field set there wins
+    pub fn merged_with(&self, overrides: &OllamaOptions) -> Self {
+        let overrides = overrides.clone();
+        OllamaOptions {
+            num_ctx: overrides.num_ctx.or(self.num_ctx),
+            num_predict: overrides.num_predict.or(self.num_predict),
+            temperature: overrides.temperature.or(self.temperature),
+            top_k: overrides.top_k.or(self.top_k),
+            top_p: overrides.top_p.or(self.top_p),
+            tfs_z: overrides.tfs_z.or(self.tfs_z),
+            repeat_penalty: overrides.repeat_penalty.or(self.repeat_penalty),
+            repeat_last_n: overrides.repeat_last_n.or(self.repeat_last_n),
+            seed: overrides.seed.or(self.seed),
+            stop: overrides.stop.or_else(|| self.stop.clone()),
+            mirostat: overrides.mirostat.or(self.mirostat),
+            mirostat_eta: overrides.mirostat_eta.or(self.mirostat_eta),
+            mirostat_tau: overrides.mirostat_tau.or(self.mirostat_tau),
+            num_gqa: overrides.num_gqa.or(self.num_gqa),
+            num_gpu: overrides.num_gpu.or(self.num_gpu),
+            num_thread: overrides.num_thread.or(self.num_thread),
+            keep_alive: overrides.keep_alive.or_else(|| self.keep_alive.clone()),
+        }
+    }
+
+    /// Convert to ollama-rs options, only setting the fields we actually have a value for
+    fn to_generation_options(&self) -> GenerationOptions {
+        let mut options = GenerationOptions::default();
+        if let Some(v) = self.num_ctx { options = options.num_ctx(v); }
+        if let Some(v) = self.num_predict { options = options.num_predict(v); }
+        if let Some(v) = self.temperature { options = options.temperature(v); }
+        if let Some(v) = self.top_k { options = options.top_k(v); }
+        if let Some(v) = self.top_p { options = options.top_p(v); }
+        if let Some(v) = self.tfs_z { options = options.tfs_z(v); }
+        if let Some(v) = self.repeat_penalty { options = options.repeat_penalty(v); }
+        if let Some(v) = self.repeat_last_n { options = options.repeat_last_n(v); }
+        if let Some(v) = self.seed { options = options.seed(v); }
+        if let Some(v) = &self.stop { options = options.stop(v.clone()); }
+        if let Some(v) = self.mirostat { options = options.mirostat(v); }
+        if let Some(v) = self.mirostat_eta { options = options.mirostat_eta(v); }
+        if let Some(v) = self.mirostat_tau { options = options.mirostat_tau(v); }
+        if let Some(v) = self.num_gqa { options = options.num_gqa(v); }
+        if let Some(v) = self.num_gpu { options = options.num_gpu(v); }
+        if let Some(v) = self.num_thread { options = options.num_thread(v); }
+        options
+    }
+}
+
+/// What /api/show reports about an installed model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OllamaModelDetails {
+    pub family: Option<String>,
+    pub families: Vec<String>,
+    /// e.g. "8.0B"
+    pub parameter_size: Option<String>,
+    /// e.g. "Q4_K_M"
+    pub quantization_level: Option<String>,
+    pub format: Option<String>,
+    /// The context window the model was trained with
+    pub trained_context_length: Option<usize>,
+    /// The context window the Modelfile asks Ollama to allocate, if it sets one
+    pub num_ctx: Option<u64>,
+    pub template: Option<String>,
+    /// As Ollama names them, e.g. "completion", "embedding", "vision", "tools". Servers older
+    /// than 0.6 don't report any.
+    pub capabilities: Vec<String>,
+}
+
+impl OllamaModelDetails {
+    fn from_show_response(body: &Value) -> Self {
+        let string = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(str::to_string);
+        let strings = |value: &Value| value.as_array()
+            .map(|values| values.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+
+        let details = &body["details"];
+        let model_info = &body["model_info"];
+
+        // Keys are prefixed with the architecture, e.g. "llama.context_length"
+        let architecture = model_info["general.architecture"].as_str().unwrap_or_default();
+        let trained_context_length = model_info[format!("{}.context_length", architecture).as_str()].as_u64()
+            .or_else(|| model_info.as_object()?.iter()
+                .find(|(key, _)| key.ends_with(".context_length"))
+                .and_then(|(_, value)| value.as_u64()))
+            .map(|length| length as usize);
+
+        // "parameters" is the Modelfile's PARAMETER lines, e.g. "num_ctx    8192"
+        let num_ctx = body["parameters"].as_str().and_then(|parameters| parameters.lines().find_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("num_ctx"), Some(value)) => value.parse().ok(),
+                _ => None,
+            }
+        }));
+
+        OllamaModelDetails {
+            family: string(&details["family"]),
+            families: strings(&details["families"]),
+            parameter_size: string(&details["parameter_size"]),
+            quantization_level: string(&details["quantization_level"]),
+            format: string(&details["format"]),
+            trained_context_length,
+            num_ctx,
+            template: string(&body["template"]),
+            capabilities: strings(&body["capabilities"]),
+        }
+    }
+
+    /// What the reported capabilities let us use the model for; empty when the server didn't say
+    pub fn model_capabilities(&self) -> Vec<ModelCapability> {
+        let reports = |name: &str| self.capabilities.iter().any(|capability| capability == name);
+        let mut capabilities = Vec::new();
+        if reports("completion") {
+            capabilities.push(ModelCapability::ChatCompletion);
+        }
+        if reports("embedding") {
+            capabilities.push(ModelCapability::Embedding);
+        }
+        capabilities
+    }
+}
+
+/// One status update from /api/pull, e.g. "pulling manifest" or a layer's download progress
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OllamaPullProgress {
+    pub status: String,
+    /// The layer being downloaded
+    #[serde(default)]
+    pub digest: Option<String>,
+    /// Size of the layer in bytes
+    #[serde(default)]
+    pub total: Option<u64>,
+    /// Bytes of the layer downloaded so far
+    #[serde(default)]
+    pub completed: Option<u64>,
+}
+
+pub type OllamaPullStream = Pin<Box<dyn Stream<Item = Result<OllamaPullProgress, AIProviderError>> + Send>>;
+
+/// An installed model and the space it takes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OllamaModelSize {
+    pub name: String,
+    pub size: u64,
+    pub modified_at: String,
+}
+
+/// How much disk space installed models take, largest first. Models built from the same base
+/// share layers, so the total can overstate what is actually on disk.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OllamaDiskUsage {
+    pub models: Vec<OllamaModelSize>,
+    pub total_bytes: u64,
+}
+
+/// A message as /api/chat takes and returns it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaChatMessage {
+    role: String,
+    #[serde(default)]
+    content: String,
+}
+
+/// The /api/chat request body. Chat goes over our own HTTP client rather than ollama-rs, which
+/// has no `keep_alive` and leaves out the retry policy and timeouts.
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaChatMessage>,
+    stream: bool,
+    options: GenerationOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<KeepAlive>,
+}
+
+/// An /api/chat answer, or one frame of a streamed one. Why generation stopped and the token
+/// counts only come with the final, `done` frame.
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    model: String,
+    message: OllamaChatMessage,
+    #[serde(default)]
+    done: bool,
+    /// "stop", or "length" when `num_predict` or the context window cut the answer off
+    done_reason: Option<String>,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+}
+
+impl OllamaChatResponse {
+    /// The finish reason of a `done` frame, "stop" when an older server doesn't say
+    fn finish_reason(&self) -> Option<String> {
+        self.done.then(|| self.done_reason.clone().unwrap_or_else(|| "stop".to_string()))
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        if !self.done {
+            return None;
+        }
+        let prompt_tokens = self.prompt_eval_count.unwrap_or_default();
+        let completion_tokens = self.eval_count.unwrap_or_default();
+        Some(TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        })
+    }
+}
+
+/// Name used in errors and retry events
+const PROVIDER_NAME: &str = "Ollama";
+
 /// Provider implementation for Ollama API
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaProvider {
     #[serde(skip)]
     client: Ollama,
+    #[serde(skip)]
+    http_client: HttpClient,
     preferred_model_name: Option<String>,
+    /// Saved default options, keyed by model name
+    #[serde(default)]
+    model_options: HashMap<String, OllamaOptions>,
+    #[serde(skip)]
+    retry_policy: RetryPolicy,
+    #[serde(skip)]
+    connection: ConnectionSettings,
+}
+
+/// Connection failures are worth retrying; anything Ollama itself answered is not
+fn is_retryable_ollama_error(error: &OllamaError) -> bool {
+    matches!(error, OllamaError::ReqwestError(e) if e.is_connect() || e.is_timeout())
+}
+
+/// The same Ollama server, reached through `http_client`
+fn with_http_client(ollama: &Ollama, http_client: HttpClient) -> Ollama {
+    let url = ollama.url();
+    let port = url.port_or_known_default().unwrap_or(11434);
+    Ollama::new_with_client(url.clone(), port, http_client)
+}
+
+fn map_ollama_error(error: OllamaError) -> AIProviderError {
+    match error {
+        OllamaError::ReqwestError(e) => network_error(PROVIDER_NAME, &e),
+        OllamaError::InternalError(e) => {
+            AIProviderError::provider(classify(None, None, None, &e.message), PROVIDER_NAME, e.message)
+        },
+        OllamaError::Other(body) => AIProviderError::Provider(Box::new(details_from_body(PROVIDER_NAME, None, &body))),
+        other => AIProviderError::APIError(other.to_string()),
+    }
 }
 
 impl OllamaProvider {
@@ -54,64 +352,228 @@ impl OllamaProvider {
             }
         };
         
+        let connection = ConnectionSettings::default();
+        let http_client = connection.build_client(HeaderMap::new())
+            .expect("Failed to create HTTP client");
+
         OllamaProvider {
-            client: ollama,
+            client: with_http_client(&ollama, http_client.clone()),
+            http_client,
             preferred_model_name: None,
+            model_options: HashMap::new(),
+            retry_policy: RetryPolicy::default(),
+            connection,
+        }
+    }
+
+    /// Replace how 429s, 5xx responses and connection failures are retried
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent to both the ollama-rs client and our own
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.http_client = connection.build_client(HeaderMap::new())?;
+        self.client = with_http_client(&self.client, self.http_client.clone());
+        self.connection = connection;
+        Ok(())
+    }
+
+    /// Replace all saved per-model option profiles
+    pub fn set_model_options(&mut self, model_options: HashMap<String, OllamaOptions>) {
+        self.model_options = model_options;
+    }
+
+    /// Save the default options for one model
+    pub fn set_options_for_model(&mut self, model_name: &str, options: OllamaOptions) {
+        self.model_options.insert(model_name.to_string(), options);
+    }
+
+    /// The model's saved profile, overridden by whatever the request sets explicitly
+    pub fn options_for_request(&self, request: &ChatCompletionRequest) -> OllamaOptions {
+        self.model_options
+            .get(&request.model)
+            .cloned()
+            .unwrap_or_default()
+            .merged_with(&OllamaOptions::from_request(request))
+    }
+
+    /// The /api/chat body for `request`, with the model's saved profile applied
+    fn build_chat_body(&self, request: &ChatCompletionRequest, stream: bool) -> OllamaChatRequest {
+        let options = self.options_for_request(request);
+        OllamaChatRequest {
+            model: request.model.clone(),
+            messages: convert_messages(&request.messages),
+            stream,
+            options: options.to_generation_options(),
+            keep_alive: options.keep_alive,
+        }
+    }
+
+    /// POST a chat body to /api/chat and check the status
+    async fn send_chat_body(&self, body: &OllamaChatRequest) -> Result<reqwest::Response, AIProviderError> {
+        let url = format!("{}api/chat", self.client.url_str());
+        log::debug!("Ollama chat request URL: {}", url);
+
+        let response = self.retry_policy.send(PROVIDER_NAME, &self.connection, self.http_client.post(&url).json(body)).await?;
+        check_response(PROVIDER_NAME, response).await
+    }
+
+    /// GET one of Ollama's JSON endpoints, e.g. "api/version". Not retried, so probes fail fast.
+    async fn get_json(&self, path: &str) -> Result<Value, AIProviderError> {
+        let url = format!("{}{}", self.client.url_str(), path);
+        let response = self.connection.send(PROVIDER_NAME, self.http_client.get(&url)).await?;
+        check_response(PROVIDER_NAME, response).await?
+            .json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse {} response: {}", path, e)))
+    }
+
+    /// POST to one of Ollama's JSON endpoints, e.g. "api/show"
+    async fn post_json(&self, path: &str, body: &Value) -> Result<Value, AIProviderError> {
+        let url = format!("{}{}", self.client.url_str(), path);
+        let response = self.retry_policy.send(PROVIDER_NAME, &self.connection, self.http_client.post(&url).json(body)).await?;
+        check_response(PROVIDER_NAME, response).await?
+            .json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse {} response: {}", path, e)))
+    }
+
+    /// Family, size, quantization, context window, template and capabilities of an installed model
+    pub async fn show_model(&self, model_name: &str) -> Result<OllamaModelDetails, AIProviderError> {
+        let body = self.post_json("api/show", &json!({ "model": model_name })).await?;
+        Ok(OllamaModelDetails::from_show_response(&body))
+    }
+
+    /// Convert listed models, asking /api/show about a few at a time. A model it can't describe
+    /// is still listed, with its capabilities and context window left unknown.
+    async fn describe_models(&self, models: Vec<LocalModel>) -> Vec<AIModel> {
+        futures::stream::iter(models)
+            .map(|model| async move {
+                let details = match self.show_model(&model.name).await {
+                    Ok(details) => Some(details),
+                    Err(e) => {
+                        log::warn!("Failed to get details for Ollama model {}: {}", model.name, e);
+                        None
+                    },
+                };
+                self.to_ai_model(model, details)
+            })
+            .buffered(4)
+            .collect()
+            .await
+    }
+
+    /// Our model for a listed one. Its context window is the one requests get: the saved
+    /// profile's num_ctx, else the Modelfile's, else what the model was trained with.
+    fn to_ai_model(&self, model: LocalModel, details: Option<OllamaModelDetails>) -> AIModel {
+        let profile_num_ctx = self.model_options.get(&model.name).and_then(|options| options.num_ctx);
+        let context_length = profile_num_ctx
+            .or_else(|| details.as_ref()?.num_ctx)
+            .map(|num_ctx| num_ctx as usize)
+            .or_else(|| details.as_ref()?.trained_context_length);
+
+        AIModel {
+            id: model.name.clone(),
+            name: model.name,
+            provider: "ollama".to_string(),
+            capabilities: details.as_ref().map(OllamaModelDetails::model_capabilities).unwrap_or_default(),
+            context_length,
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: json!({
+                "size": model.size,
+                "modified_at": model.modified_at,
+                "details": details,
+            }),
         }
     }
+
+    /// Download `model_name` from the Ollama library, streaming progress. The NDJSON is read by
+    /// hand, as ollama-rs expects each network chunk to hold exactly one status line. Downloads
+    /// can run far past the total timeout, so only the idle timeout applies.
+    pub async fn pull_model(&self, model_name: &str) -> Result<OllamaPullStream, AIProviderError> {
+        let connection = ConnectionSettings { total_timeout_ms: Some(0), ..self.connection.clone() };
+        let http_client = connection.build_client(HeaderMap::new())?;
+        let url = format!("{}api/pull", self.client.url_str());
+        // Older servers read the model from "name"
+        let body = json!({ "model": model_name, "name": model_name, "stream": true });
+
+        let response = self.retry_policy.send(PROVIDER_NAME, &connection, http_client.post(&url).json(&body)).await?;
+        let response = check_response(PROVIDER_NAME, response).await?;
+
+        let progress = json_lines_stream(PROVIDER_NAME, response, connection.idle_timeout()).map(|line| {
+            let line = line?;
+            let frame: Value = serde_json::from_str(&line)
+                .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse pull progress: {}: {}", e, line)))?;
+            // A failure partway, e.g. an unknown model name, arrives as {"error": "..."}
+            if frame.get("error").is_some() {
+                return Err(AIProviderError::Provider(Box::new(details_from_body(PROVIDER_NAME, None, &line))));
+            }
+            serde_json::from_value(frame)
+                .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse pull progress: {}: {}", e, line)))
+        });
+
+        Ok(Box::pin(progress))
+    }
+
+    /// Remove an installed model
+    pub async fn delete_model(&self, model_name: &str) -> Result<(), AIProviderError> {
+        self.client.delete_model(model_name.to_string()).await.map_err(map_ollama_error)
+    }
+
+    /// Install a copy of `source` under the name `destination`
+    pub async fn copy_model(&self, source: &str, destination: &str) -> Result<(), AIProviderError> {
+        self.client.copy_model(source.to_string(), destination.to_string()).await.map_err(map_ollama_error)
+    }
+
+    /// The size of every installed model
+    pub async fn disk_usage(&self) -> Result<OllamaDiskUsage, AIProviderError> {
+        let models = self.retry_policy.run(PROVIDER_NAME, || self.client.list_local_models(), is_retryable_ollama_error).await
+        .map_err(map_ollama_error)?;
+
+        let mut models: Vec<OllamaModelSize> = models.into_iter()
+            .map(|model| OllamaModelSize { name: model.name, size: model.size, modified_at: model.modified_at })
+            .collect();
+        models.sort_by_key(|model| std::cmp::Reverse(model.size));
+        let total_bytes = models.iter().map(|model| model.size).sum();
+        Ok(OllamaDiskUsage { models, total_bytes })
+    }
+
+    /// Names of the models loaded into memory right now
+    pub async fn running_models(&self) -> Result<Vec<String>, AIProviderError> {
+        let body = self.get_json("api/ps").await?;
+        Ok(body["models"].as_array()
+            .map(|models| models.iter().filter_map(|model| model["name"].as_str().map(str::to_string)).collect())
+            .unwrap_or_default())
+    }
 }
 
 #[async_trait]
 impl ModelProvider for OllamaProvider {
     async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
         // Use the actual ollama-rs API
-        let response = self.client.list_local_models().await
-        .map_err(|e| AIProviderError::APIError(format!("Failed to list models: {}", e)))?;
+        let response = self.retry_policy.run(PROVIDER_NAME, || self.client.list_local_models(), is_retryable_ollama_error).await
+        .map_err(map_ollama_error)?;
         
         // Convert to our model format
-        let models = response
-        .into_iter()
-        .map(|m| AIModel {
-            id: m.name.clone(),
-            name: m.name,
-            provider: "ollama".to_string(),
-            capabilities: vec![
-            ModelCapability::ChatCompletion,
-            ModelCapability::Embedding,
-            ],
-            context_length: None,
-            additional_info: serde_json::Value::Null,
-        })
-        .collect();
-        
-        Ok(models)
+        Ok(self.describe_models(response).await)
     }
     
     async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
         // Get the list of models and find the requested one
-        let models = self.client.list_local_models().await
-        .map_err(|e| AIProviderError::APIError(format!("Failed to list models: {}", e)))?;
+        let models = self.retry_policy.run(PROVIDER_NAME, || self.client.list_local_models(), is_retryable_ollama_error).await
+        .map_err(map_ollama_error)?;
         
         let model = models.into_iter()
         .find(|m| m.name == model_id)
         .ok_or_else(|| AIProviderError::ModelNotAvailable(model_id.to_string()))?;
         
-        Ok(AIModel {
-            id: model.name.clone(),
-            name: model.name,
-            provider: "ollama".to_string(),
-            capabilities: vec![
-            ModelCapability::ChatCompletion,
-            ModelCapability::Embedding,
-            ],
-            context_length: None,
-            additional_info: serde_json::Value::Null,
-        })
+        Ok(self.describe_models(vec![model]).await.remove(0))
     }
 
     async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
-        let all_models = self.list_models().await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to list models: {}", e)))?;
+        let preference_model = self.preferred_model_name.as_deref().unwrap_or(preference_model);
+        let all_models = self.list_models().await?;
         
         // First try to find the requested model
         for model in &all_models {
@@ -137,8 +599,8 @@ impl ModelProvider for OllamaProvider {
             }
         }
         
-        // Last resort - use first available model
-        if let Some(first_model) = all_models.first() {
+        // Last resort - use first available model that can chat, or may be able to
+        if let Some(first_model) = all_models.iter().find(|model| can_chat(model)) {
             log::warn!("No preferred models found, using first available model: {}", first_model.name);
             return Ok(first_model.clone());
         }
@@ -150,11 +612,11 @@ impl ModelProvider for OllamaProvider {
             id: "llama3.2:latest".to_string(),
             name: "llama3.2:latest".to_string(),
             provider: "ollama".to_string(),
-            capabilities: vec![
-                ModelCapability::ChatCompletion,
-                ModelCapability::Embedding,
-            ],
+            capabilities: vec![ModelCapability::ChatCompletion],
             context_length: None,
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
             additional_info: serde_json::Value::Null,
         })
     }
@@ -168,7 +630,25 @@ impl ModelProvider for OllamaProvider {
     fn get_provider_name(&self) -> String {
         "Ollama".to_string()
     }
-        
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+
+    /// Asks /api/version first, as it's cheap, then lists installed and loaded models
+    async fn health_check(&self) -> HealthStatus {
+        let started = Instant::now();
+        let version = match self.get_json("api/version").await {
+            Ok(body) => body["version"].as_str().map(str::to_string),
+            Err(e) => return HealthStatus::failed(self.get_provider_name(), started.elapsed(), e),
+        };
+        let latency = started.elapsed();
+
+        let mut status = HealthStatus::from_models(self.get_provider_name(), latency, self.list_models().await);
+        status.version = version;
+        status.loaded_models = self.running_models().await.ok();
+        status
+    }
 }
 
 #[async_trait]
@@ -177,155 +657,103 @@ impl ChatCompletionProvider for OllamaProvider {
         &self,
         request: &ChatCompletionRequest,
     ) -> Result<ChatCompletionResponse, AIProviderError> {
-        // Combine messages into a simple prompt as in the example
-        let messages = convert_messages(&request.messages);
-        //let ollama = Ollama::default();
-        let model = request.model.clone();
-        //let __prompt = "<system>You are just a text completion engine. Do not precede your response with '...'</system>When the core reciprocating GPU-based mechanical-electrical engine connected to the network, we found that the farm operations were ".to_string();
-        let prompt = messages_to_prompt(&request.messages);
-        //let temp = Preferences::load(app_state, app_handle)
-        let temp = request.temperature.unwrap_or(0.2);
-        let max_tokens = request.max_tokens.unwrap_or(10) as i32;
-        let options = GenerationOptions::default()
-        .temperature(temp)
-        .repeat_penalty(1.5)
-        .top_k(25)
-        .num_predict(max_tokens)
-        .top_p(0.25);
-    
-        let request = GenerationRequest::new(model, prompt.clone()).options(options);
-        //println!("***Request: {:?}", request.clone());
-        //println!("***Prompt: {:?}", prompt.clone());
-        let res = self.client
-            .generate(request)
-            .await
-            .map_err(|e| AIProviderError::APIError(format!("Failed to generate generate response /api/generate completion: {}", e)))?;
+        // Send the conversation as real role-tagged messages so the model's chat template applies
+        let body = self.build_chat_body(request, false);
+        let response: OllamaChatResponse = self.send_chat_body(&body).await?
+            .json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse response: {}", e)))?;
 
-        // let res = ollama
-        //     .generate(GenerationRequest::new(model, prompt).options(options))
-        //     .await;
-    
-        //println!("Response: {:?}", res);
+        let usage = response.usage();
+        let finish_reason = response.finish_reason();
 
         Ok(ChatCompletionResponse {
             id: Uuid::new_v4().to_string(),
             created: chrono::Utc::now().timestamp() as u64,
-            model: res.model,
+            model: response.model,
             choices: vec![ChatCompletionChoice {
                 index: 0,
-                message: crate::ai::models::ChatMessage {
-                    role: MessageRole::Assistant,
-                    content: res.response,
-                    name: None,
-                },
-                finish_reason: Some("stop".to_string()),
+                message: convert_ollama_message(&response.message),
+                finish_reason,
             }],
-            usage: None,
+            usage,
         })
-
-        // OLD OLD OLD using /api/chat
-        // Create and send the request
-        // let chat_request = ChatMessageRequest::new(request.model.clone(), messages);
-        // let response = self.client.send_chat_messages(chat_request).await
-        // .map_err(|e| AIProviderError::APIError(format!("Chat completion failed: {}", e)))?;
-        
-        // Ok(ChatCompletionResponse {
-        //     id: uuid::Uuid::new_v4().to_string(),
-        //     created: chrono::Utc::now().timestamp() as u64,
-        //     model: request.model.clone(),
-        //     choices: vec![ChatCompletionChoice {
-        //         index: 0,
-        //         message: crate::ai::models::ChatMessage {
-        //             role: MessageRole::Assistant,
-        //             content: response.message.content,
-        //             name: None,
-        //         },
-        //         finish_reason: Some("stop".to_string()),
-        //     }],
-        //     usage: None,
-        // })
     }
     
     async fn create_streaming_chat_completion(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send>>, AIProviderError> {
-        // Combine messages into a simple prompt
-        Err(AIProviderError::NotImplemented("Streaming not implemented for Ollama".to_string()))
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let body = self.build_chat_body(request, true);
+        let response = self.send_chat_body(&body).await?;
+
+        // Ollama doesn't give its frames an id, so every frame of this stream shares one
+        let id = Uuid::new_v4().to_string();
+        let created = chrono::Utc::now().timestamp() as u64;
+
+        let mapped_stream = json_lines_stream(PROVIDER_NAME, response, self.connection.idle_timeout()).map(move |line| {
+            let line = line?;
+            let frame: OllamaChatResponse = serde_json::from_str(&line)
+                .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse stream frame: {}: {}", e, line)))?;
+            Ok(convert_ollama_stream_frame(&id, created, frame))
+        });
+
+        Ok(Box::pin(mapped_stream))
+    }
+}
+
+/// Convert one NDJSON frame from /api/chat into our generic chunk format
+fn convert_ollama_stream_frame(id: &str, created: u64, frame: OllamaChatResponse) -> ChatCompletionChunk {
+    let message = convert_ollama_message(&frame.message);
+
+    // Usage only arrives with the final `done` frame
+    let usage = frame.usage();
+
+    ChatCompletionChunk {
+        id: id.to_string(),
+        created,
+        choices: vec![ChatCompletionChunkChoice {
+            delta: ChatMessageDelta {
+                role: Some(message.role),
+                content: if message.content.is_empty() { None } else { Some(message.content) },
+            },
+            finish_reason: frame.finish_reason(),
+            index: 0,
+        }],
+        usage,
     }
 }
 
-fn convert_messages(messages: &[crate::ai::models::ChatMessage]) -> Vec<ChatMessage> {
+fn convert_messages(messages: &[ChatMessage]) -> Vec<OllamaChatMessage> {
     messages
     .iter()
     .map(|msg| {
-        match msg.role {
-            MessageRole::User => ChatMessage::user(msg.content.clone()),
-            MessageRole::Assistant => ChatMessage::assistant(msg.content.clone()),
-            MessageRole::System => ChatMessage::system(msg.content.clone()),
-            MessageRole::Tool => ChatMessage::user(msg.content.clone()), // Fallback for tool
-            MessageRole::Function => ChatMessage::user(msg.content.clone()), // Fallback for function
-        }
+        let role = match msg.role {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+            MessageRole::System => "system",
+            MessageRole::Tool => "tool",
+            MessageRole::Function => "user", // Fallback for function
+        };
+        OllamaChatMessage { role: role.to_string(), content: msg.content.clone() }
     })
     .collect()
 }
 
-fn convert_ollama_message(msg: &ollama_rs::generation::chat::ChatMessage) -> crate::ai::models::ChatMessage {
-    let role = match msg.role {
-        ollama_rs::generation::chat::MessageRole::User => MessageRole::User,
-        ollama_rs::generation::chat::MessageRole::Assistant => MessageRole::Assistant,
-        ollama_rs::generation::chat::MessageRole::System => MessageRole::System,
-        ollama_rs::generation::chat::MessageRole::Tool => MessageRole::Tool,
+fn convert_ollama_message(msg: &OllamaChatMessage) -> ChatMessage {
+    let role = match msg.role.as_str() {
+        "user" => MessageRole::User,
+        "system" => MessageRole::System,
+        "tool" => MessageRole::Tool,
+        _ => MessageRole::Assistant,
     };
     
-    crate::ai::models::ChatMessage {
+    ChatMessage {
         role,
         content: msg.content.clone(),
         name: None, // Ollama doesn't provide a name
     }
 }
 
-/// Converts a list of chat messages to a formatted string prompt
-fn messages_to_prompt(messages: &[crate::ai::models::ChatMessage]) -> String {
-    let mut prompt = String::new();
-    
-    for message in messages {
-        match message.role {
-            MessageRole::System => {
-                // System messages are typically wrapped in special tags
-                prompt.push_str(&format!("<system>{}</system>\n", message.content));
-            },
-            MessageRole::User => {
-                // Format user messages
-                prompt.push_str(&format!("<user>{}</user>\n", message.content));
-            },
-            MessageRole::Assistant => {
-                // Format assistant responses
-                //prompt.push_str(&format!("<assistant>{}</assistant>\n", message.content));
-            },
-            MessageRole::Tool => {
-                // Handle tool/function messages as generic content with a tag
-                prompt.push_str(&format!("<{role}>{content}</{role}>\n", 
-                    role = "tool".to_string(),
-                    content = message.content));
-            },
-            MessageRole::Function => {
-                // Handle tool/function messages as generic content with a tag
-                prompt.push_str(&format!("<{role}>{content}</{role}>\n", 
-                    role = "function".to_string(),
-                    content = message.content));
-            }
-        }
-    }
-    
-    // Add a final assistant prefix to prompt the model to continue
-    if !messages.is_empty() && messages.last().unwrap().role != MessageRole::Assistant {
-        prompt.push_str("Assistant: ");
-    }
-    
-    prompt
-}
-
 #[async_trait]
 impl EmbeddingProvider for OllamaProvider {
     async fn create_embeddings(
@@ -334,16 +762,19 @@ impl EmbeddingProvider for OllamaProvider {
     ) -> Result<Vec<Embedding>, AIProviderError> {
 
         let mut embeddings: Vec<Embedding> = Vec::new();
-        let input = EmbeddingsInput::Multiple(embedding_request.input.clone());
-        
-        // Create a request to generate embeddings
-        let request = GenerateEmbeddingsRequest::new(
-            embedding_request.model.to_string(),
-            input,
-        );
-        
-        let response = self.client.generate_embeddings(request).await
-        .map_err(|e| AIProviderError::APIError(format!("Embedding failed: {}", e)))?;
+
+        // Create a request to generate embeddings; the request isn't Clone, so each attempt builds its own
+        let send_request = || {
+            let input = EmbeddingsInput::Multiple(embedding_request.input.clone());
+            let request = GenerateEmbeddingsRequest::new(
+                embedding_request.model.to_string(),
+                input,
+            );
+            self.client.generate_embeddings(request)
+        };
+
+        let response = self.retry_policy.run(PROVIDER_NAME, send_request, is_retryable_ollama_error).await
+        .map_err(map_ollama_error)?;
         
         let embedding_model_name = PreferredEmbeddingModel::get_preferred_embedding_model(self);
 
@@ -367,4 +798,6 @@ impl PreferredEmbeddingModel for OllamaProvider {
         "nomic-embed-text".to_string()
         //unimplemented!("Ollama does not yet support a preferred embedding model");
     }
-}
\ No newline at end of file
+}
+
+impl AIProvider for OllamaProvider {}
@@ -0,0 +1,597 @@
+use crate::ai::{
+    traits::{ModelProvider, ChatCompletionProvider, EmbeddingProvider, PreferredEmbeddingModel, AIProvider, AIProviderError, AIErrorKind, ChatCompletionStream},
+    models::*,
+};
+use async_trait::async_trait;
+use reqwest::{Client as HttpClient, header};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use futures::StreamExt;
+use super::sse::sse_data_stream;
+use super::retry::RetryPolicy;
+use super::connection::ConnectionSettings;
+use super::http_error::check_response;
+use std::collections::HashMap;
+
+/// A saved OpenAI compatible endpoint: OpenRouter, Groq, Together, vLLM, llama.cpp's server,
+/// a corporate gateway and so on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpenAICompatibleConfig {
+    /// Display name, also used to pick the endpoint in `transform_text`
+    pub name: String,
+    /// Base URL including the version segment, e.g. `https://openrouter.ai/api/v1`
+    pub base_url: String,
+    /// Sent as a bearer token when present
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Extra headers sent with every request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Extra query parameters sent with every request, e.g. Azure's `api-version`
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    /// Model to use for embeddings, if the endpoint serves any
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+}
+
+/// Provider for any server speaking the OpenAI chat completions wire format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAICompatibleProvider {
+    #[serde(skip)]
+    client: HttpClient,
+    name: String,
+    provider_id: String,
+    base_url: String,
+    #[serde(skip)]
+    api_key: Option<String>,
+    #[serde(skip)]
+    query: Vec<(String, String)>,
+    #[serde(skip)]
+    embedding_model: Option<String>,
+    /// Send the length limit as `max_completion_tokens`, which OpenAI's reasoning models require
+    #[serde(skip)]
+    max_completion_tokens: bool,
+    preferred_model_name: Option<String>,
+    #[serde(skip)]
+    retry_policy: RetryPolicy,
+    /// Kept so the client can be rebuilt when the connection settings change
+    #[serde(skip)]
+    default_headers: header::HeaderMap,
+    #[serde(skip)]
+    connection: ConnectionSettings,
+}
+
+impl OpenAICompatibleProvider {
+    /// Create a provider for the endpoint described by `config`
+    pub fn new(config: &OpenAICompatibleConfig) -> Self {
+        let mut headers = header::HeaderMap::new();
+
+        // Add content-type header
+        headers.insert(
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
+        );
+
+        for (name, value) in &config.headers {
+            match (
+                header::HeaderName::from_bytes(name.as_bytes()),
+                header::HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                },
+                _ => log::warn!("Skipping invalid header '{}' for endpoint {}", name, config.name),
+            }
+        }
+
+        let connection = ConnectionSettings::default();
+        let client = connection.build_client(headers.clone())
+            .expect("Failed to create HTTP client");
+
+        OpenAICompatibleProvider {
+            client,
+            name: config.name.clone(),
+            provider_id: format!("openai_compatible:{}", config.name),
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.clone().filter(|key| !key.is_empty()),
+            query: config.query.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            embedding_model: config.embedding_model.clone(),
+            max_completion_tokens: false,
+            preferred_model_name: None,
+            retry_policy: RetryPolicy::default(),
+            default_headers: headers,
+            connection,
+        }
+    }
+
+    /// Replace how 429s, 5xx responses and connection failures are retried
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Apply timeouts, proxy, CA bundle and user-agent, rebuilding the HTTP client
+    pub fn set_connection_settings(&mut self, connection: ConnectionSettings) -> Result<(), AIProviderError> {
+        self.client = connection.build_client(self.default_headers.clone())?;
+        self.connection = connection;
+        Ok(())
+    }
+
+    /// Use a different id for `get_provider_name` and the `provider` tag on models
+    pub fn with_provider_id(mut self, provider_id: &str) -> Self {
+        self.provider_id = provider_id.to_string();
+        self
+    }
+
+    /// Send the length limit as `max_completion_tokens` rather than `max_tokens`
+    pub(crate) fn with_max_completion_tokens(mut self) -> Self {
+        self.max_completion_tokens = true;
+        self
+    }
+
+    /// The same endpoint under another base URL, sharing the client, e.g. one Azure deployment
+    pub(crate) fn with_base_url(&self, base_url: &str) -> Self {
+        OpenAICompatibleProvider { base_url: base_url.trim_end_matches('/').to_string(), ..self.clone() }
+    }
+
+    /// A copy for health probes: the probe timeouts, and no retries
+    pub(crate) fn for_probe(&self) -> Result<Self, AIProviderError> {
+        let mut probe = self.clone();
+        probe.set_retry_policy(RetryPolicy::none());
+        probe.set_connection_settings(self.connection.for_probe())?;
+        Ok(probe)
+    }
+
+    /// The endpoint's display name
+    pub fn get_display_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The server's root, without the "/v1" of the OpenAI routes
+    fn server_root(&self) -> &str {
+        self.base_url.strip_suffix("/v1").unwrap_or(&self.base_url)
+    }
+
+    /// GET JSON from `path` under the server's root, for servers with a native API next to the
+    /// OpenAI one, e.g. LM Studio's "api/v0/models"
+    pub(crate) async fn get_native_json(&self, path: &str) -> Result<Value, AIProviderError> {
+        let url = format!("{}/{}", self.server_root(), path);
+        let response = self.retry_policy.send(&self.name, &self.connection, self.add_endpoint_params(self.client.get(&url))).await?;
+        check_response(&self.name, response).await?
+            .json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse {} response: {}", path, e)))
+    }
+
+    /// POST JSON to `path` under the server's root. Not retried and without a first-byte
+    /// timeout, as these are actions like loading a model: slow, and not to be done twice.
+    pub(crate) async fn post_native_json(&self, path: &str, body: &Value) -> Result<Value, AIProviderError> {
+        let url = format!("{}/{}", self.server_root(), path);
+        let connection = ConnectionSettings { first_byte_timeout_ms: Some(0), ..self.connection.clone() };
+        let response = connection.send(&self.name, self.add_endpoint_params(self.client.post(&url).json(body))).await?;
+        check_response(&self.name, response).await?
+            .json().await
+            .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse {} response: {}", path, e)))
+    }
+
+    /// Add the bearer token, if an API key is set, and the endpoint's extra query parameters
+    fn add_endpoint_params(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let builder = builder.query(&self.query);
+        match &self.api_key {
+            Some(key) => builder.header("Authorization", format!("Bearer {}", key)),
+            None => builder,
+        }
+    }
+
+    /// POST a chat request and hand back the response if the status is good
+    async fn send_chat_request(&self, body: &OpenAICompatibleRequest) -> Result<reqwest::Response, AIProviderError> {
+        let url = format!("{}/chat/completions", self.base_url);
+
+        // Debug: Print the serialized JSON for inspection
+        if log::log_enabled!(log::Level::Debug) {
+            if let Ok(json_string) = serde_json::to_string_pretty(body) {
+                log::debug!("{} request URL: {}", self.name, url);
+                log::debug!("{} request JSON: {}", self.name, json_string);
+            }
+        }
+
+        let http_request = self.add_endpoint_params(self.client.post(&url).json(body));
+        let response = self.retry_policy.send(&self.name, &self.connection, http_request).await?;
+
+        check_response(&self.name, response).await
+    }
+}
+
+// Request body for the /chat/completions endpoint
+#[derive(Serialize, Debug)]
+struct OpenAICompatibleMessage {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct OpenAICompatibleStreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct OpenAICompatibleRequest {
+    model: String,
+    messages: Vec<OpenAICompatibleMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<OpenAICompatibleStreamOptions>,
+}
+
+impl OpenAICompatibleRequest {
+    /// Build the wire request shared by the streaming and non-streaming calls
+    fn from_request(request: &ChatCompletionRequest, stream: bool, max_completion_tokens: bool) -> Self {
+        OpenAICompatibleRequest {
+            model: request.model.clone(),
+            messages: request.messages.iter()
+                .map(|msg| OpenAICompatibleMessage {
+                    role: role_to_openai(&msg.role),
+                    content: msg.content.clone(),
+                    name: msg.name.clone(),
+                })
+                .collect(),
+            temperature: request.temperature,
+            max_tokens: request.max_tokens.filter(|_| !max_completion_tokens),
+            max_completion_tokens: request.max_tokens.filter(|_| max_completion_tokens),
+            top_p: request.top_p,
+            stop: request.stop.clone(),
+            seed: request.seed,
+            stream,
+            // Ask for a final usage-only chunk so streaming callers get token counts too
+            stream_options: if stream { Some(OpenAICompatibleStreamOptions { include_usage: true }) } else { None },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+impl From<OpenAICompatibleUsage> for TokenUsage {
+    fn from(usage: OpenAICompatibleUsage) -> Self {
+        TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleResponseMessage {
+    role: String,
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleResponseChoice {
+    message: OpenAICompatibleResponseMessage,
+    finish_reason: Option<String>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleResponse {
+    id: String,
+    created: u64,
+    model: String,
+    choices: Vec<OpenAICompatibleResponseChoice>,
+    usage: Option<OpenAICompatibleUsage>,
+}
+
+// One server-sent event from a streaming /chat/completions call
+#[derive(Deserialize)]
+struct OpenAICompatibleStreamDelta {
+    role: Option<String>,
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleStreamChoice {
+    delta: OpenAICompatibleStreamDelta,
+    finish_reason: Option<String>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleStreamChunk {
+    id: String,
+    created: u64,
+    #[serde(default)]
+    choices: Vec<OpenAICompatibleStreamChoice>,
+    usage: Option<OpenAICompatibleUsage>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct OpenAICompatibleModelData {
+    id: String,
+    #[serde(default)]
+    object: Option<String>,
+    created: Option<u64>,
+    owned_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleModelList {
+    data: Vec<OpenAICompatibleModelData>,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleEmbeddingData {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAICompatibleEmbeddingResponse {
+    data: Vec<OpenAICompatibleEmbeddingData>,
+}
+
+fn role_to_openai(role: &MessageRole) -> String {
+    match role {
+        MessageRole::System => "system".to_string(),
+        MessageRole::User => "user".to_string(),
+        MessageRole::Assistant => "assistant".to_string(),
+        MessageRole::Tool => "tool".to_string(),
+        MessageRole::Function => "function".to_string(),
+    }
+}
+
+fn role_from_openai(role: &str) -> MessageRole {
+    match role {
+        "system" => MessageRole::System,
+        "user" => MessageRole::User,
+        "assistant" => MessageRole::Assistant,
+        "tool" => MessageRole::Tool,
+        "function" => MessageRole::Function,
+        _ => MessageRole::Assistant, // Default
+    }
+}
+
+/// Convert a non-streaming /chat/completions body into our generic format
+fn convert_openai_compatible_response(response: OpenAICompatibleResponse) -> ChatCompletionResponse {
+    ChatCompletionResponse {
+        id: response.id,
+        choices: response.choices.into_iter()
+            .map(|choice| ChatCompletionChoice {
+                message: ChatMessage {
+                    role: role_from_openai(&choice.message.role),
+                    content: choice.message.content.unwrap_or_default(),
+                    name: None,
+                },
+                finish_reason: choice.finish_reason,
+                index: choice.index,
+            })
+            .collect(),
+        created: response.created,
+        model: response.model,
+        usage: response.usage.map(TokenUsage::from),
+    }
+}
+
+/// Convert one streamed `data:` payload into our generic chunk format
+fn convert_openai_compatible_stream_chunk(data: &str) -> Result<ChatCompletionChunk, AIProviderError> {
+    let chunk: OpenAICompatibleStreamChunk = serde_json::from_str(data)
+        .map_err(|e| AIProviderError::DeserializationError(format!("Failed to parse stream chunk: {}: {}", e, data)))?;
+
+    Ok(ChatCompletionChunk {
+        id: chunk.id,
+        created: chunk.created,
+        choices: chunk.choices.into_iter()
+            .map(|choice| ChatCompletionChunkChoice {
+                delta: ChatMessageDelta {
+                    role: choice.delta.role.as_deref().map(role_from_openai),
+                    content: choice.delta.content,
+                },
+                finish_reason: choice.finish_reason,
+                index: choice.index,
+            })
+            .collect(),
+        usage: chunk.usage.map(TokenUsage::from),
+    })
+}
+
+impl OpenAICompatibleProvider {
+    fn convert_model(&self, model_data: OpenAICompatibleModelData) -> AIModel {
+        AIModel {
+            id: model_data.id.clone(),
+            name: model_data.id.clone(),
+            provider: self.provider_id.clone(),
+            capabilities: vec![ModelCapability::ChatCompletion], // Most served models support chat
+            context_length: None, // Not provided by the API
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: serde_json::to_value(model_data).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for OpenAICompatibleProvider {
+    async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+        let url = format!("{}/models", self.base_url);
+        log::debug!("{} list models URL: {}", self.name, url);
+
+        let response = self.retry_policy.send(&self.name, &self.connection, self.add_endpoint_params(self.client.get(&url))).await?;
+        let response = check_response(&self.name, response).await?;
+
+        let model_list: OpenAICompatibleModelList = response.json().await
+            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(model_list.data.into_iter()
+            .map(|m| self.convert_model(m))
+            .collect())
+    }
+
+    async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+        let url = format!("{}/models/{}", self.base_url, model_id);
+
+        let response = self.retry_policy.send(&self.name, &self.connection, self.add_endpoint_params(self.client.get(&url))).await?;
+        let response = match check_response(&self.name, response).await {
+            Ok(response) => response,
+            // Not every server looks up single models, and ids like "openai/gpt-4o" don't fit the path
+            Err(e) if e.kind() == AIErrorKind::ModelNotFound || e.details().and_then(|details| details.status) == Some(404) => {
+                return self.list_models().await?.into_iter()
+                    .find(|model| model.id == model_id)
+                    .ok_or_else(|| match e.kind() {
+                        AIErrorKind::ModelNotFound => e,
+                        _ => AIProviderError::ModelNotFound(model_id.to_string()),
+                    });
+            },
+            Err(e) => return Err(e),
+        };
+
+        let model_data: Value = response.json().await
+            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(AIModel {
+            id: model_data["id"].as_str().unwrap_or(model_id).to_string(),
+            name: model_data["id"].as_str().unwrap_or(model_id).to_string(),
+            provider: self.provider_id.clone(),
+            capabilities: vec![ModelCapability::ChatCompletion],
+            context_length: None, // Not provided by the API
+            max_output_tokens: None,
+            pricing: None,
+            deprecation_date: None,
+            additional_info: model_data,
+        })
+    }
+
+    async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+        // Use the preferred_model_name if set, otherwise use the provided preference_model
+        let model_id = match &self.preferred_model_name {
+            Some(model) => model.clone(),
+            None => preference_model.to_string(),
+        };
+
+        // Try to fetch the model info
+        match self.get_model(&model_id).await {
+            Ok(model) => Ok(model),
+            Err(e) if e.kind() == AIErrorKind::ModelNotFound => {
+                // If model isn't available, try to get the first available model
+                let models = self.list_models().await?;
+                models.into_iter().next()
+                    .ok_or_else(|| AIProviderError::ModelNotAvailable(format!(
+                        "No models available for {}", self.name
+                    )))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError> {
+        self.preferred_model_name = Some(model_name);
+        Ok(())
+    }
+
+    fn get_provider_name(&self) -> String {
+        self.provider_id.clone()
+    }
+
+    fn get_preferred_model_name(&self) -> Option<String> {
+        self.preferred_model_name.clone()
+    }
+}
+
+#[async_trait]
+impl ChatCompletionProvider for OpenAICompatibleProvider {
+    async fn create_chat_completion(
+        &self,
+        request: &ChatCompletionRequest
+    ) -> Result<ChatCompletionResponse, AIProviderError> {
+        let body = OpenAICompatibleRequest::from_request(request, false, self.max_completion_tokens);
+        let response = self.send_chat_request(&body).await?;
+
+        let parsed: OpenAICompatibleResponse = response.json().await
+            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(convert_openai_compatible_response(parsed))
+    }
+
+    async fn create_streaming_chat_completion(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, AIProviderError> {
+        let body = OpenAICompatibleRequest::from_request(request, true, self.max_completion_tokens);
+        let response = self.send_chat_request(&body).await?;
+
+        // Each server-sent `data:` line is one OpenAI style chunk
+        let stream = sse_data_stream(&self.name, response, self.connection.idle_timeout())
+            .map(|data| data.and_then(|data| convert_openai_compatible_stream_chunk(&data)));
+
+        Ok(Box::pin(stream))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAICompatibleProvider {
+    async fn create_embeddings(
+        &self,
+        embedding_request: EmbeddingRequest,
+    ) -> Result<Vec<Embedding>, AIProviderError> {
+        let url = format!("{}/embeddings", self.base_url);
+
+        let request_body = json!({
+            "model": embedding_request.model,
+            "input": embedding_request.input,
+        });
+
+        let http_request = self.add_endpoint_params(self.client.post(&url).json(&request_body));
+        let response = self.retry_policy.send(&self.name, &self.connection, http_request).await?;
+
+        // Many local LLM servers don't support embeddings
+        if response.status().as_u16() == 404 {
+            return Err(AIProviderError::ModelNotAvailable(
+                format!("Embeddings not supported by {}", self.name)
+            ));
+        }
+        let response = check_response(&self.name, response).await?;
+
+        let embedding_response: OpenAICompatibleEmbeddingResponse = response.json().await
+            .map_err(|e| AIProviderError::APIError(format!("Failed to parse response: {}", e)))?;
+
+        Ok(embedding_response.data.into_iter()
+            .map(|e| Embedding {
+                vector: e.embedding,
+                index: e.index,
+                model_name: Some(embedding_request.model.clone()),
+            })
+            .collect())
+    }
+}
+
+impl PreferredEmbeddingModel for OpenAICompatibleProvider {
+    fn get_preferred_embedding_model(&self) -> String {
+        self.embedding_model.clone().unwrap_or_default()
+    }
+}
+
+impl AIProvider for OpenAICompatibleProvider {}
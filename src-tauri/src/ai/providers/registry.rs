@@ -0,0 +1,397 @@
+use crate::ai::traits::AIProviderError;
+use super::{
+    Provider,
+    OpenAIProvider,
+    LMStudioProvider,
+    OllamaProvider,
+    InceptionLabsProvider,
+    AnthropicProvider,
+    GeminiProvider,
+    OpenAICompatibleProvider,
+    OpenAICompatibleConfig,
+    AzureOpenAIProvider,
+    AzureOpenAIConfig,
+    MockProvider,
+    MockFixtures,
+};
+use super::inception_labs_provider::INCEPTION_LABS_DEFAULT_URL;
+use super::anthropic_provider::ANTHROPIC_DEFAULT_URL;
+use super::gemini_provider::GEMINI_DEFAULT_URL;
+use super::azure_openai_provider::AZURE_OPENAI_DEFAULT_API_VERSION;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Builds a provider from its JSON configuration. Providers from outside this crate return `Provider::Custom`.
+pub type ProviderFactory = Arc<dyn Fn(&Value) -> Result<Provider, AIProviderError> + Send + Sync>;
+
+/// What kind of value a configuration field holds, so a settings UI knows how to ask for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigFieldKind {
+    /// A secret, looked up from `env_var` or the saved credential `credential_key` when not given
+    ApiKey,
+    Url,
+    Text,
+    Path,
+    /// A JSON object, e.g. a table of headers or deployments
+    Object,
+}
+
+/// One field of a provider's configuration
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigField {
+    pub name: String,
+    pub kind: ConfigFieldKind,
+    pub required: bool,
+    pub description: String,
+    pub default: Option<String>,
+    pub env_var: Option<String>,
+    pub credential_key: Option<String>,
+}
+
+impl ConfigField {
+    pub fn new(name: &str, kind: ConfigFieldKind, description: &str) -> Self {
+        ConfigField {
+            name: name.to_string(),
+            kind,
+            required: false,
+            description: description.to_string(),
+            default: None,
+            env_var: None,
+            credential_key: None,
+        }
+    }
+
+    /// A required `api_key` field
+    pub fn api_key(env_var: &str, credential_key: &str) -> Self {
+        ConfigField {
+            required: true,
+            env_var: Some(env_var.to_string()),
+            credential_key: Some(credential_key.to_string()),
+            ..ConfigField::new("api_key", ConfigFieldKind::ApiKey, "API key")
+        }
+    }
+
+    pub fn url(name: &str, description: &str, default: &str) -> Self {
+        ConfigField {
+            default: Some(default.to_string()),
+            ..ConfigField::new(name, ConfigFieldKind::Url, description)
+        }
+    }
+
+    pub fn text(name: &str, description: &str) -> Self {
+        ConfigField::new(name, ConfigFieldKind::Text, description)
+    }
+
+    pub fn path(name: &str, description: &str) -> Self {
+        ConfigField::new(name, ConfigFieldKind::Path, description)
+    }
+
+    pub fn object(name: &str, description: &str) -> Self {
+        ConfigField::new(name, ConfigFieldKind::Object, description)
+    }
+
+    pub fn required(self) -> Self {
+        ConfigField { required: true, ..self }
+    }
+}
+
+/// A registered provider as shown to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderDescriptor {
+    /// Id passed as `provider_type`, e.g. "OpenAI"
+    pub id: String,
+    pub display_name: String,
+    pub config_schema: Vec<ConfigField>,
+}
+
+/// Maps provider ids to factories and config schemas
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    entries: Vec<(ProviderDescriptor, ProviderFactory)>,
+}
+
+impl std::fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.entries.iter().map(|(descriptor, _)| &descriptor.id)).finish()
+    }
+}
+
+impl ProviderRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding every provider in this crate
+    pub fn with_builtin_providers() -> Self {
+        let mut registry = Self::new();
+
+        registry.register(
+            descriptor("OpenAI", "OpenAI", vec![ConfigField::api_key("OPENAI_API_KEY", "openai_api_key")]),
+            |config| Ok(Provider::OpenAI(OpenAIProvider::new(&required_str(config, "api_key")?))),
+        );
+        registry.register(
+            descriptor("LMStudio", "LM Studio", vec![
+                ConfigField::url("base_url", "LM Studio server URL", "http://localhost:1234/v1/"),
+            ]),
+            |config| {
+                let base_url = optional_str(config, "base_url").unwrap_or_else(|| "http://localhost:1234/v1/".to_string());
+                Ok(Provider::LMStudio(LMStudioProvider::new(&base_url, None)))
+            },
+        );
+        registry.register(
+            descriptor("Ollama", "Ollama", vec![
+                ConfigField::url("base_url", "Ollama server URL", "http://localhost:11434"),
+            ]),
+            |config| {
+                let base_url = optional_str(config, "base_url").unwrap_or_else(|| "http://localhost:11434".to_string());
+                Ok(Provider::Ollama(OllamaProvider::new(&base_url)))
+            },
+        );
+        registry.register(
+            descriptor("InceptionLabs", "Inception Labs", vec![
+                ConfigField::api_key("INCEPTION_API_KEY", "inception_api_key"),
+                ConfigField::url("api_url", "API URL", INCEPTION_LABS_DEFAULT_URL),
+            ]),
+            |config| Ok(Provider::InceptionLabs(InceptionLabsProvider::new(
+                required_str(config, "api_key")?,
+                optional_str(config, "api_url").unwrap_or_default(),
+            ))),
+        );
+        registry.register(
+            descriptor("Anthropic", "Anthropic", vec![
+                ConfigField::api_key("ANTHROPIC_API_KEY", "anthropic_api_key"),
+                ConfigField::url("base_url", "API URL", ANTHROPIC_DEFAULT_URL),
+            ]),
+            |config| {
+                let api_key = required_str(config, "api_key")?;
+                Ok(Provider::Anthropic(match optional_str(config, "base_url") {
+                    Some(base_url) => AnthropicProvider::with_base_url(&api_key, &base_url),
+                    None => AnthropicProvider::new(&api_key),
+                }))
+            },
+        );
+        registry.register(
+            descriptor("Gemini", "Google Gemini", vec![
+                ConfigField::api_key("GEMINI_API_KEY", "gemini_api_key"),
+                ConfigField::url("base_url", "API URL", GEMINI_DEFAULT_URL),
+            ]),
+            |config| {
+                let api_key = required_str(config, "api_key")?;
+                Ok(Provider::Gemini(match optional_str(config, "base_url") {
+                    Some(base_url) => GeminiProvider::with_base_url(&api_key, &base_url),
+                    None => GeminiProvider::new(&api_key),
+                }))
+            },
+        );
+        registry.register(
+            descriptor("OpenAICompatible", "OpenAI compatible endpoint", vec![
+                ConfigField::text("name", "Endpoint name").required(),
+                ConfigField::new("base_url", ConfigFieldKind::Url, "Base URL, including /v1").required(),
+                ConfigField::new("api_key", ConfigFieldKind::ApiKey, "API key, if the server needs one"),
+                ConfigField::object("headers", "Extra headers sent with every request"),
+                ConfigField::text("embedding_model", "Model to use for embeddings"),
+            ]),
+            |config| {
+                let endpoint: OpenAICompatibleConfig = from_config(config)?;
+                Ok(Provider::OpenAICompatible(OpenAICompatibleProvider::new(&endpoint)))
+            },
+        );
+        registry.register(
+            descriptor("AzureOpenAI", "Azure OpenAI", vec![
+                ConfigField::new("endpoint", ConfigFieldKind::Url, "Resource endpoint").required(),
+                ConfigField::api_key("AZURE_OPENAI_API_KEY", "azure_openai_api_key"),
+                ConfigField { default: Some(AZURE_OPENAI_DEFAULT_API_VERSION.to_string()), ..ConfigField::text("api_version", "API version") },
+                ConfigField::object("deployments", "Model name to deployment name"),
+                ConfigField::text("embedding_model", "Model to use for embeddings"),
+            ]),
+            |config| {
+                let azure_config: AzureOpenAIConfig = from_config(config)?;
+                Ok(Provider::AzureOpenAI(AzureOpenAIProvider::new(&azure_config)))
+            },
+        );
+        registry.register(
+            descriptor("Mock", "Mock (offline fixtures)", vec![
+                ConfigField::path("fixtures_path", "YAML fixtures file; without one the mock echoes prompts"),
+            ]),
+            |config| {
+                let fixtures = match optional_str(config, "fixtures_path") {
+                    Some(path) => MockFixtures::from_yaml_file(path)?,
+                    None => MockFixtures::default(),
+                };
+                Ok(Provider::Mock(MockProvider::new(fixtures)))
+            },
+        );
+
+        registry
+    }
+
+    /// Add a provider, replacing any registered under the same id
+    pub fn register<F>(&mut self, descriptor: ProviderDescriptor, factory: F)
+    where
+        F: Fn(&Value) -> Result<Provider, AIProviderError> + Send + Sync + 'static,
+    {
+        self.entries.retain(|(existing, _)| existing.id != descriptor.id);
+        self.entries.push((descriptor, Arc::new(factory)));
+    }
+
+    /// Every registered provider, in registration order
+    pub fn descriptors(&self) -> Vec<ProviderDescriptor> {
+        self.entries.iter().map(|(descriptor, _)| descriptor.clone()).collect()
+    }
+
+    pub fn descriptor(&self, id: &str) -> Result<&ProviderDescriptor, AIProviderError> {
+        self.entries.iter()
+            .find(|(descriptor, _)| descriptor.id == id)
+            .map(|(descriptor, _)| descriptor)
+            .ok_or_else(|| self.unknown_provider(id))
+    }
+
+    /// Build the provider registered as `id`, once every field its schema requires is set
+    pub fn create(&self, id: &str, config: &Value) -> Result<Provider, AIProviderError> {
+        let (descriptor, factory) = self.entries.iter()
+            .find(|(descriptor, _)| descriptor.id == id)
+            .ok_or_else(|| self.unknown_provider(id))?;
+        for field in descriptor.config_schema.iter().filter(|field| field.required) {
+            if config.get(&field.name).is_none_or(|value| value.is_null() || value == "") {
+                return Err(missing_setting(&field.name));
+            }
+        }
+        factory(config)
+    }
+
+    fn unknown_provider(&self, id: &str) -> AIProviderError {
+        let ids: Vec<&str> = self.entries.iter().map(|(descriptor, _)| descriptor.id.as_str()).collect();
+        AIProviderError::InvalidRequest(format!(
+            "Unknown provider '{}'. Available providers: {}", id, ids.join(", ")
+        ))
+    }
+}
+
+fn descriptor(id: &str, display_name: &str, config_schema: Vec<ConfigField>) -> ProviderDescriptor {
+    ProviderDescriptor {
+        id: id.to_string(),
+        display_name: display_name.to_string(),
+        config_schema,
+    }
+}
+
+/// A non-empty string field of `config`
+pub(crate) fn optional_str(config: &Value, name: &str) -> Option<String> {
+    config.get(name)
+        .and_then(Value::as_str)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+pub(crate) fn required_str(config: &Value, name: &str) -> Result<String, AIProviderError> {
+    optional_str(config, name).ok_or_else(|| missing_setting(name))
+}
+
+fn missing_setting(name: &str) -> AIProviderError {
+    AIProviderError::InvalidRequest(format!("Missing provider setting: {}", name))
+}
+
+fn from_config<T: serde::de::DeserializeOwned>(config: &Value) -> Result<T, AIProviderError> {
+    serde_json::from_value(config.clone())
+        .map_err(|e| AIProviderError::InvalidRequest(format!("Invalid provider settings: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::traits::{AIErrorKind, ModelProvider};
+    use serde_json::json;
+
+    /// Just the required fields of `descriptor`, filled with plausible values
+    fn minimal_config(descriptor: &ProviderDescriptor) -> Value {
+        let mut config = json!({});
+        for field in descriptor.config_schema.iter().filter(|field| field.required) {
+            config[&field.name] = match field.kind {
+                ConfigFieldKind::Url => json!("https://example.com/v1"),
+                ConfigFieldKind::Object => json!({}),
+                _ => json!("test"),
+            };
+        }
+        config
+    }
+
+    #[test]
+    fn every_builtin_provider_builds_from_its_required_fields() {
+        let registry = ProviderRegistry::with_builtin_providers();
+        let expected = [
+            ("OpenAI", "openai"),
+            ("LMStudio", "lm_studio"),
+            ("Ollama", "Ollama"),
+            ("InceptionLabs", "inception_labs"),
+            ("Anthropic", "anthropic"),
+            ("Gemini", "gemini"),
+            ("OpenAICompatible", "openai_compatible:test"),
+            ("AzureOpenAI", "azure_openai"),
+            ("Mock", "mock"),
+        ];
+
+        let ids: Vec<String> = registry.descriptors().into_iter().map(|descriptor| descriptor.id).collect();
+        assert_eq!(ids, expected.map(|(id, _)| id));
+
+        for (id, provider_name) in expected {
+            let descriptor = registry.descriptor(id).unwrap();
+            let provider = registry.create(id, &minimal_config(descriptor))
+                .unwrap_or_else(|e| panic!("{} didn't build: {}", id, e));
+            assert_eq!(provider.get_provider_name(), provider_name);
+        }
+    }
+
+    #[test]
+    fn missing_required_fields_are_rejected() {
+        let registry = ProviderRegistry::with_builtin_providers();
+        for descriptor in registry.descriptors() {
+            for field in descriptor.config_schema.iter().filter(|field| field.required) {
+                let mut config = minimal_config(&descriptor);
+                for blank in [Value::Null, json!("")] {
+                    config[&field.name] = blank;
+                    let error = registry.create(&descriptor.id, &config).unwrap_err();
+                    assert_eq!(error.kind(), AIErrorKind::InvalidRequest);
+                    assert!(error.to_string().contains(&format!("Missing provider setting: {}", field.name)), "{}: {}", descriptor.id, error);
+                }
+                config.as_object_mut().unwrap().remove(&field.name);
+                assert!(registry.create(&descriptor.id, &config).is_err(), "{} built without {}", descriptor.id, field.name);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_providers_list_the_registered_ones() {
+        let registry = ProviderRegistry::with_builtin_providers();
+        let error = registry.create("Nope", &json!({})).unwrap_err();
+        assert!(error.to_string().contains("Unknown provider 'Nope'. Available providers: OpenAI, LMStudio"), "{}", error);
+        assert!(registry.descriptor("Nope").is_err());
+    }
+
+    #[test]
+    fn registering_an_id_again_replaces_it() {
+        let mut registry = ProviderRegistry::with_builtin_providers();
+        let count = registry.descriptors().len();
+        registry.register(
+            descriptor("Ollama", "Ollama on the LAN", vec![ConfigField::text("host", "Host").required()]),
+            |config| Ok(Provider::Ollama(OllamaProvider::new(&format!("http://{}:11434", required_str(config, "host")?)))),
+        );
+
+        assert_eq!(registry.descriptors().len(), count);
+        assert_eq!(registry.descriptor("Ollama").unwrap().display_name, "Ollama on the LAN");
+        assert!(registry.create("Ollama", &json!({})).is_err());
+        assert!(registry.create("Ollama", &json!({"host": "10.0.0.2"})).is_ok());
+    }
+
+    #[test]
+    fn optional_str_skips_blank_and_non_string_values() {
+        let config = json!({"a": "x", "b": "", "c": 3});
+        assert_eq!(optional_str(&config, "a").as_deref(), Some("x"));
+        assert_eq!(optional_str(&config, "b"), None);
+        assert_eq!(optional_str(&config, "c"), None);
+        assert_eq!(optional_str(&config, "d"), None);
+        assert!(required_str(&config, "b").is_err());
+    }
+}
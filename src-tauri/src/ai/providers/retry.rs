@@ -0,0 +1,413 @@
+use crate::ai::traits::AIProviderError;
+use super::http_error::network_error;
+use super::connection::ConnectionSettings;
+use reqwest::header::HeaderMap;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Reported before each retry, so the UI can show that a request is being retried
+#[derive(Debug, Clone, Serialize)]
+pub struct RetryEvent {
+    pub provider: String,
+    /// The attempt about to be made, counting the first try as 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+pub type RetryObserver = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// How often and how patiently a provider retries 429s, 5xx responses and connection failures
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 disables retrying
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Fraction of each backoff that is randomized, from 0.0 to 1.0
+    pub jitter: f64,
+    /// Longest Retry-After we wait out; when a server asks for more we give up instead
+    pub max_retry_after: Duration,
+    observer: Option<RetryObserver>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(20),
+            multiplier: 2.0,
+            jitter: 0.25,
+            max_retry_after: Duration::from_secs(60),
+            observer: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .field("multiplier", &self.multiplier)
+            .field("jitter", &self.jitter)
+            .field("max_retry_after", &self.max_retry_after)
+            .finish()
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        RetryPolicy { max_attempts: 1, ..Default::default() }
+    }
+
+    /// Call `observer` before every retry
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RetryEvent) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// The jittered wait before retry number `retry` (1 for the first retry)
+    pub fn backoff_delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(30) as i32;
+        let base = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let capped = base.min(self.max_backoff.as_secs_f64());
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        Duration::from_secs_f64(capped * (1.0 - jitter + jitter * random))
+    }
+
+    fn notify(&self, provider: &str, attempt: u32, delay: Duration, reason: &str) {
+        log::warn!(
+            "{}: retrying (attempt {} of {}) in {:?} after {}",
+            provider, attempt, self.max_attempts, delay, reason
+        );
+        if let Some(observer) = &self.observer {
+            observer(&RetryEvent {
+                provider: provider.to_string(),
+                attempt,
+                max_attempts: self.max_attempts,
+                delay_ms: delay.as_millis() as u64,
+                reason: reason.to_string(),
+            });
+        }
+    }
+
+    /// Send `request`, retrying connection failures and 429/5xx responses. Retry-After and
+    /// x-ratelimit-reset headers are honored. The last response is returned whatever its
+    /// status, so callers map statuses to errors as before.
+    pub(crate) async fn send(&self, provider: &str, connection: &ConnectionSettings, request: reqwest::RequestBuilder) -> Result<reqwest::Response, AIProviderError> {
+        let mut attempt = 1;
+        loop {
+            let this_try = request.try_clone()
+                .ok_or_else(|| AIProviderError::InvalidRequest("Request body can't be retried".to_string()))?;
+            let can_retry = attempt < self.max_attempts;
+
+            match connection.first_byte(provider, this_try.send()).await {
+                Ok(Ok(response)) => {
+                    let status = response.status();
+                    if !can_retry || !(status.as_u16() == 429 || status.is_server_error()) {
+                        return Ok(response);
+                    }
+
+                    let delay = match retry_after(response.headers()) {
+                        Some(wait) if wait > self.max_retry_after => return Ok(response),
+                        Some(wait) => wait,
+                        None => self.backoff_delay(attempt),
+                    };
+                    attempt += 1;
+                    self.notify(provider, attempt, delay, &status.to_string());
+                    tokio::time::sleep(delay).await;
+                },
+                Ok(Err(e)) if can_retry && (e.is_connect() || e.is_timeout()) => {
+                    let delay = self.backoff_delay(attempt);
+                    attempt += 1;
+                    self.notify(provider, attempt, delay, &format!("network error: {}", e));
+                    tokio::time::sleep(delay).await;
+                },
+                Ok(Err(e)) => return Err(network_error(provider, &e)),
+                // No response headers within the first-byte timeout
+                Err(timeout) if can_retry => {
+                    let delay = self.backoff_delay(attempt);
+                    attempt += 1;
+                    self.notify(provider, attempt, delay, &timeout.to_string());
+                    tokio::time::sleep(delay).await;
+                },
+                Err(timeout) => return Err(timeout),
+            }
+        }
+    }
+
+    /// Run `operation` until it succeeds, fails in a way `should_retry` rejects, or attempts run out.
+    /// For clients that hide the HTTP response, so no Retry-After is available.
+    pub(crate) async fn run<T, E, F, Fut>(&self, provider: &str, mut operation: F, should_retry: impl Fn(&E) -> bool) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(e) if attempt < self.max_attempts && should_retry(&e) => {
+                    let delay = self.backoff_delay(attempt);
+                    attempt += 1;
+                    self.notify(provider, attempt, delay, &e.to_string());
+                    tokio::time::sleep(delay).await;
+                },
+                result => return result,
+            }
+        }
+    }
+}
+
+/// How long the server asked us to wait, from `Retry-After`, `retry-after-ms` or the
+/// `x-ratelimit-reset*` family. With several reset headers the longest wait wins.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(str::trim);
+
+    if let Some(ms) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+    }
+
+    if let Some(value) = header("retry-after") {
+        if let Ok(seconds) = value.parse::<f64>() {
+            return Some(Duration::from_secs_f64(seconds.max(0.0)));
+        }
+        // Otherwise an HTTP date
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+            let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+            return Some(wait.to_std().unwrap_or_default());
+        }
+    }
+
+    ["x-ratelimit-reset", "x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"].iter()
+        .filter_map(|name| header(name))
+        .filter_map(parse_reset)
+        .max()
+}
+
+/// A reset header is either seconds, a Unix timestamp, or a duration such as "6m0s" or "20ms"
+fn parse_reset(value: &str) -> Option<Duration> {
+    if let Ok(number) = value.parse::<f64>() {
+        // Anything this large is a timestamp rather than a wait
+        if number > 1_000_000_000.0 {
+            let wait = number - chrono::Utc::now().timestamp() as f64;
+            return Some(Duration::from_secs_f64(wait.max(0.0)));
+        }
+        return Some(Duration::from_secs_f64(number.max(0.0)));
+    }
+
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.'))?;
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += number * match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::test_server::{reply, serve};
+    use crate::ai::traits::AIErrorKind;
+    use reqwest::header::HeaderValue;
+    use std::sync::Mutex;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs.iter()
+            .map(|(name, value)| (reqwest::header::HeaderName::from_static(name), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    /// A policy that retries at once, recording each retry it announces
+    fn quick_policy(max_attempts: u32) -> (RetryPolicy, Arc<Mutex<Vec<RetryEvent>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let policy = RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            jitter: 0.0,
+            ..Default::default()
+        }.with_observer(move |event| recorded.lock().unwrap().push(event.clone()));
+        (policy, events)
+    }
+
+    async fn send(policy: &RetryPolicy, base_url: &str) -> Result<reqwest::Response, AIProviderError> {
+        let request = reqwest::Client::new().post(format!("{}/chat/completions", base_url)).body("{}");
+        policy.send("Test", &ConnectionSettings::default(), request).await
+    }
+
+    #[test]
+    fn retry_after_reads_delta_seconds_and_http_dates() {
+        assert_eq!(retry_after(&headers(&[("retry-after", "2")])), Some(Duration::from_secs(2)));
+        assert_eq!(retry_after(&headers(&[("retry-after", "1.5")])), Some(Duration::from_millis(1500)));
+
+        let in_thirty_seconds = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        let wait = retry_after(&headers(&[("retry-after", &in_thirty_seconds)])).unwrap();
+        assert!(wait > Duration::from_secs(28) && wait <= Duration::from_secs(30), "{:?}", wait);
+
+        let in_the_past = (chrono::Utc::now() - chrono::Duration::seconds(30)).to_rfc2822();
+        assert_eq!(retry_after(&headers(&[("retry-after", &in_the_past)])), Some(Duration::ZERO));
+
+        assert_eq!(retry_after(&headers(&[("retry-after", "soon")])), None);
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn retry_after_ms_wins_over_retry_after() {
+        let wait = retry_after(&headers(&[("retry-after-ms", "250"), ("retry-after", "10")]));
+        assert_eq!(wait, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn rate_limit_resets_take_the_longest_wait() {
+        let wait = retry_after(&headers(&[
+            ("x-ratelimit-reset-requests", "1m30s"),
+            ("x-ratelimit-reset-tokens", "20ms"),
+        ]));
+        assert_eq!(wait, Some(Duration::from_secs(90)));
+
+        assert_eq!(parse_reset("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset("1h2m3.5s"), Some(Duration::from_secs_f64(3723.5)));
+        assert_eq!(parse_reset("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_reset("12"), Some(Duration::from_secs(12)));
+        assert_eq!(parse_reset("3 days"), None);
+
+        let timestamp = (chrono::Utc::now().timestamp() + 10).to_string();
+        let wait = retry_after(&headers(&[("x-ratelimit-reset", &timestamp)])).unwrap();
+        assert!(wait > Duration::from_secs(8) && wait <= Duration::from_secs(10), "{:?}", wait);
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+            multiplier: 2.0,
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff_delay(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff_delay(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff_delay(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff_delay(30), Duration::from_millis(300));
+
+        let jittered = RetryPolicy { jitter: 0.5, ..policy }.backoff_delay(1);
+        assert!(jittered >= Duration::from_millis(50) && jittered <= Duration::from_millis(100), "{:?}", jittered);
+    }
+
+    #[tokio::test]
+    async fn send_waits_out_retry_after_then_succeeds() {
+        let (base_url, server) = serve(vec![
+            reply("429 Too Many Requests", &[("retry-after-ms", "40")], r#"{"error":{"type":"rate_limit_error"}}"#),
+            reply("200 OK", &[], "{}"),
+        ]).await;
+        let (policy, events) = quick_policy(3);
+
+        let response = send(&policy, &base_url).await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(server.await.unwrap().len(), 2);
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].attempt, events[0].max_attempts, events[0].delay_ms), (2, 3, 40));
+        assert!(events[0].reason.contains("429"));
+    }
+
+    #[tokio::test]
+    async fn send_retries_server_errors_until_attempts_run_out() {
+        let (base_url, server) = serve(vec![
+            reply("500 Internal Server Error", &[], ""),
+            reply("502 Bad Gateway", &[], ""),
+            reply("503 Service Unavailable", &[], ""),
+        ]).await;
+        let (policy, events) = quick_policy(3);
+
+        // The last response is handed back for the caller to classify
+        let response = send(&policy, &base_url).await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(server.await.unwrap().len(), 3);
+        let attempts: Vec<u32> = events.lock().unwrap().iter().map(|event| event.attempt).collect();
+        assert_eq!(attempts, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn send_does_not_retry_client_errors() {
+        for status in ["400 Bad Request", "401 Unauthorized", "403 Forbidden", "404 Not Found", "422 Unprocessable Entity"] {
+            let (base_url, server) = serve(vec![reply(status, &[], "")]).await;
+            let (policy, events) = quick_policy(3);
+
+            let response = send(&policy, &base_url).await.unwrap();
+
+            assert_eq!(response.status().as_u16().to_string(), status[..3]);
+            assert_eq!(server.await.unwrap().len(), 1);
+            assert!(events.lock().unwrap().is_empty(), "{} was retried", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn send_gives_up_when_retry_after_is_too_long() {
+        let (base_url, server) = serve(vec![reply("429 Too Many Requests", &[("retry-after", "120")], "")]).await;
+        let (policy, events) = quick_policy(3);
+        let policy = RetryPolicy { max_retry_after: Duration::from_secs(60), ..policy };
+
+        let response = send(&policy, &base_url).await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(server.await.unwrap().len(), 1);
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn send_retries_refused_connections() {
+        // A port nothing listens on any more
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let (policy, events) = quick_policy(2);
+
+        let error = send(&policy, &base_url).await.unwrap_err();
+
+        assert_eq!(error.kind(), AIErrorKind::Network);
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn single_attempt_policy_never_retries() {
+        let (base_url, server) = serve(vec![reply("503 Service Unavailable", &[("retry-after", "0")], "")]).await;
+        let (policy, events) = quick_policy(1);
+
+        let response = send(&policy, &base_url).await.unwrap();
+
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(server.await.unwrap().len(), 1);
+        assert!(events.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ai::traits::AIProviderError;
+use super::http_error::network_error;
+use super::connection::idle_timeout_stream;
+use futures::{stream, Stream, StreamExt};
+use std::time::Duration;
+
+/// Turn a streaming HTTP response into the payloads of its server-sent `data:` lines.
+///
+/// Comment lines, `event:` lines and blank separators are skipped. The OpenAI-style
+/// `[DONE]` terminator ends the stream.
+pub(crate) fn sse_data_stream(
+    provider: &str,
+    response: reqwest::Response,
+    idle_timeout: Option<Duration>,
+) -> impl Stream<Item = Result<String, AIProviderError>> + Send {
+    line_stream(provider, response, idle_timeout)
+        .filter_map(|line| async move {
+            match line {
+                Ok(line) => parse_data_line(&line).map(Ok),
+                Err(e) => Some(Err(e)),
+            }
+        })
+        .take_while(|data| {
+            let done = matches!(data, Ok(data) if data == "[DONE]");
+            async move { !done }
+        })
+}
+
+/// Turn a streaming HTTP response made of newline-delimited JSON into its non-empty lines
+pub(crate) fn json_lines_stream(
+    provider: &str,
+    response: reqwest::Response,
+    idle_timeout: Option<Duration>,
+) -> impl Stream<Item = Result<String, AIProviderError>> + Send {
+    line_stream(provider, response, idle_timeout).filter(|line| {
+        let blank = matches!(line, Ok(line) if line.trim().is_empty());
+        async move { !blank }
+    })
+}
+
+/// Split a streaming HTTP response into lines, however the bytes happen to be chunked.
+/// `provider` names the source in errors when the connection drops or goes quiet for
+/// longer than `idle_timeout` mid-stream.
+fn line_stream(
+    provider: &str,
+    response: reqwest::Response,
+    idle_timeout: Option<Duration>,
+) -> impl Stream<Item = Result<String, AIProviderError>> + Send {
+    let provider_name = provider.to_string();
+    let bytes = Box::pin(response.bytes_stream().map(move |chunk| chunk.map_err(|e| network_error(&provider_name, &e))));
+    let bytes = Box::pin(idle_timeout_stream(provider, idle_timeout, bytes));
+
+    stream::unfold(
+        (bytes, Vec::<u8>::new(), false),
+        |(mut bytes, mut buffer, mut finished)| async move {
+            loop {
+                // Hand out any complete line we already have buffered
+                if let Some(pos) = buffer.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=pos).collect();
+                    return Some((Ok(decode_line(&line)), (bytes, buffer, finished)));
+                }
+
+                if finished {
+                    // The server closed the connection without a trailing newline
+                    if buffer.is_empty() {
+                        return None;
+                    }
+                    let line = std::mem::take(&mut buffer);
+                    return Some((Ok(decode_line(&line)), (bytes, buffer, finished)));
+                }
+
+                match bytes.next().await {
+                    Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                    Some(Err(e)) => {
+                        buffer.clear();
+                        return Some((Err(e), (bytes, buffer, true)));
+                    }
+                    None => finished = true,
+                }
+            }
+        },
+    )
+}
+
+fn decode_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line).trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// Extract the payload of a single `data:` line, if that's what this line is
+fn parse_data_line(line: &str) -> Option<String> {
+    line.strip_prefix("data:")
+        .map(|data| data.strip_prefix(' ').unwrap_or(data).to_string())
+}
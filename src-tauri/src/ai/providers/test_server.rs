@@ -0,0 +1,71 @@
+//! A local HTTP server answering with canned responses, for testing providers offline
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// What the test server was sent
+pub(crate) struct CapturedRequest {
+    /// Request line and headers, lowercased
+    pub head: String,
+    pub body: Value,
+}
+
+/// An HTTP response with `status`, e.g. "429 Too Many Requests", extra `headers` and `body`
+pub(crate) fn reply(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let headers: String = headers.iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect();
+    format!(
+        "HTTP/1.1 {}\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}",
+        status, headers, body.len(), body
+    )
+}
+
+/// Answer one connection per response in `responses`, in order. Returns the base URL to point
+/// a provider at, and a handle that yields the requests once every response has been sent.
+pub(crate) async fn serve(responses: Vec<String>) -> (String, JoinHandle<Vec<CapturedRequest>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            requests.push(read_request(&mut socket).await);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+        requests
+    });
+
+    (base_url, server)
+}
+
+/// Answer a single request with `status` and a `content_type` body
+pub(crate) async fn serve_once(status: &str, content_type: &str, body: &str) -> (String, JoinHandle<CapturedRequest>) {
+    let (base_url, server) = serve(vec![reply(status, &[("content-type", content_type)], body)]).await;
+    let server = tokio::spawn(async move { server.await.unwrap().remove(0) });
+    (base_url, server)
+}
+
+/// Read one request: its head, then as much body as its content-length announces
+async fn read_request(socket: &mut TcpStream) -> CapturedRequest {
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let read = socket.read(&mut buffer).await.unwrap();
+        assert!(read > 0, "Connection closed before the request was complete");
+        received.extend_from_slice(&buffer[..read]);
+
+        let text = String::from_utf8_lossy(&received).to_string();
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let content_length = head.lines()
+                .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse().unwrap()))
+                .unwrap_or(0);
+            if body.len() >= content_length {
+                return CapturedRequest { head: head.to_lowercase(), body: serde_json::from_str(body).unwrap_or_default() };
+            }
+        }
+    }
+}
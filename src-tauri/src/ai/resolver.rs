@@ -0,0 +1,227 @@
+use crate::ai::models::{AIModel, ModelCapability};
+use crate::ai::traits::{AIErrorKind, AIProviderError, ModelProvider};
+use serde::{Serialize, Deserialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The user's model choices
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelPreferences {
+    /// Model to use with any provider that serves it, when nothing more specific is set
+    pub default_model: Option<String>,
+    /// Preferred model per registered provider id, e.g. "Ollama" -> "llama3.1:8b"
+    pub providers: HashMap<String, String>,
+}
+
+impl ModelPreferences {
+    pub fn preferred_for(&self, provider_id: &str) -> Option<&str> {
+        self.providers.get(provider_id).map(String::as_str).filter(|model| !model.is_empty())
+    }
+}
+
+/// Which step of the resolution picked a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ModelSource {
+    /// Named by the request, or by a fallback chain step
+    Explicit,
+    /// The provider's `preferred_model_name`
+    ProviderPreferred,
+    /// The user's default model
+    UserDefault,
+    /// Picked from what the provider lists, with the model catalog's help
+    CatalogFallback,
+}
+
+/// A model the provider was checked to serve, and how it was picked
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedModel {
+    pub model: AIModel,
+    pub source: ModelSource,
+}
+
+/// Whether `model` can be used for chat. Unknown capabilities count, as not every server says
+/// what its models can do.
+pub fn can_chat(model: &AIModel) -> bool {
+    model.capabilities.is_empty() || model.capabilities.contains(&ModelCapability::ChatCompletion)
+}
+
+/// Picks the model to chat with on a provider: the one the request names, else the provider's
+/// preferred model, else the user's default, else the best chat model the provider lists.
+/// A named model must exist and be able to chat; preferences that don't fit this provider are
+/// skipped.
+#[derive(Debug, Clone, Default)]
+pub struct ModelResolver {
+    default_model: Option<String>,
+}
+
+impl ModelResolver {
+    pub fn new(default_model: Option<String>) -> Self {
+        ModelResolver { default_model: default_model.filter(|model| !model.is_empty()) }
+    }
+
+    pub async fn resolve<P: ModelProvider + ?Sized>(&self, provider: &P, explicit: Option<&str>) -> Result<ResolvedModel, AIProviderError> {
+        if let Some(model_id) = explicit.filter(|model| !model.is_empty()) {
+            let model = provider.get_model(model_id).await?;
+            if !can_chat(&model) {
+                return Err(AIProviderError::InvalidRequest(format!("{} can't be used for chat", model_id)));
+            }
+            return Ok(ResolvedModel { model, source: ModelSource::Explicit });
+        }
+
+        let preferences = [
+            (provider.get_preferred_model_name(), ModelSource::ProviderPreferred),
+            (self.default_model.clone(), ModelSource::UserDefault),
+        ];
+        for (model_id, source) in preferences {
+            let Some(model_id) = model_id.filter(|model| !model.is_empty()) else {
+                continue;
+            };
+            match provider.get_model(&model_id).await {
+                Ok(model) if can_chat(&model) => return Ok(ResolvedModel { model, source }),
+                Ok(_) => log::warn!("{} can't be used for chat, skipping it", model_id),
+                Err(e) if e.kind() == AIErrorKind::ModelNotFound => {
+                    log::info!("{} doesn't serve {}, skipping it", provider.get_provider_name(), model_id);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+
+        let today = chrono::Utc::now().date_naive();
+        provider.list_models().await?
+            .into_iter()
+            .filter(can_chat)
+            .filter(|model| model.deprecation_date.is_none_or(|date| date > today))
+            .min_by(fallback_order)
+            .map(|model| ResolvedModel { model, source: ModelSource::CatalogFallback })
+            .ok_or_else(|| AIProviderError::ModelNotAvailable(format!("No chat models available for {}", provider.get_provider_name())))
+    }
+}
+
+/// Models known to chat before those that may, cheapest first, then the shortest id, as
+/// dated and variant ids share their base model's catalog entry. Ties keep the listing order.
+fn fallback_order(a: &AIModel, b: &AIModel) -> Ordering {
+    let price = |model: &AIModel| model.pricing.as_ref().map_or(f64::INFINITY, |pricing| pricing.input + pricing.output);
+    a.capabilities.is_empty().cmp(&b.capabilities.is_empty())
+        .then(price(a).total_cmp(&price(b)))
+        .then(a.id.len().cmp(&b.id.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::models::ModelPricing;
+    use crate::ai::providers::mock_provider::test_support::mock;
+    use async_trait::async_trait;
+    use chrono::{Duration, Utc};
+
+    /// Lists `models` as they are, prices and deprecation dates included
+    struct Listing(Vec<AIModel>);
+
+    #[async_trait]
+    impl ModelProvider for Listing {
+        async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError> {
+            Ok(self.0.clone())
+        }
+
+        async fn get_model(&self, model_id: &str) -> Result<AIModel, AIProviderError> {
+            self.0.iter().find(|model| model.id == model_id).cloned()
+                .ok_or_else(|| AIProviderError::ModelNotFound(model_id.to_string()))
+        }
+
+        async fn get_preferred_inference_model(&self, preference_model: &str) -> Result<AIModel, AIProviderError> {
+            self.get_model(preference_model).await
+        }
+
+        fn get_provider_name(&self) -> String {
+            "listing".to_string()
+        }
+
+        fn set_preferred_inference_model(&mut self, _model_name: String) -> Result<(), AIProviderError> {
+            Ok(())
+        }
+    }
+
+    fn model(id: &str, capabilities: Vec<ModelCapability>, price: Option<f64>) -> AIModel {
+        AIModel {
+            id: id.to_string(),
+            name: id.to_string(),
+            provider: "listing".to_string(),
+            capabilities,
+            context_length: None,
+            max_output_tokens: None,
+            pricing: price.map(|price| ModelPricing { input: price, output: price, cached_input: None }),
+            deprecation_date: None,
+            additional_info: serde_json::Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver_skips_preferences_the_provider_lacks() {
+        let mut provider = mock("models: [{id: embedder, capabilities: [Embedding]}, {id: chat-model}, {id: chat-model-large}]");
+        provider.set_preferred_inference_model("missing-model".to_string()).unwrap();
+
+        let resolved = ModelResolver::new(Some("also-missing".to_string())).resolve(&provider, None).await.unwrap();
+        assert_eq!(resolved.model.id, "chat-model");
+        assert_eq!(resolved.source, ModelSource::CatalogFallback);
+
+        let resolved = ModelResolver::new(Some("chat-model-large".to_string())).resolve(&provider, None).await.unwrap();
+        assert_eq!(resolved.model.id, "chat-model-large");
+        assert_eq!(resolved.source, ModelSource::UserDefault);
+
+        let error = ModelResolver::default().resolve(&provider, Some("missing-model")).await.unwrap_err();
+        assert_eq!(error.kind(), AIErrorKind::ModelNotFound);
+
+        let error = ModelResolver::default().resolve(&provider, Some("embedder")).await.unwrap_err();
+        assert_eq!(error.kind(), AIErrorKind::InvalidRequest);
+    }
+
+    #[test]
+    fn fallback_order_prefers_known_chat_models_then_price_then_short_ids() {
+        let chat = || vec![ModelCapability::ChatCompletion];
+        let mut models = [
+            model("unknown-cheap", Vec::new(), Some(0.1)),
+            model("chat-unpriced", chat(), None),
+            model("chat-pricey", chat(), Some(10.0)),
+            model("chat-cheap-2024-08-06", chat(), Some(1.0)),
+            model("chat-cheap", chat(), Some(1.0)),
+            model("unknown", Vec::new(), None),
+        ];
+
+        models.sort_by(fallback_order);
+
+        let ids: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(ids, ["chat-cheap", "chat-cheap-2024-08-06", "chat-pricey", "chat-unpriced", "unknown-cheap", "unknown"]);
+    }
+
+    #[test]
+    fn fallback_order_keeps_the_listing_order_on_ties() {
+        let mut models = [model("model-b", Vec::new(), None), model("model-a", Vec::new(), None)];
+        models.sort_by(fallback_order);
+        assert_eq!(models[0].id, "model-b");
+    }
+
+    #[tokio::test]
+    async fn catalog_fallback_skips_retired_models() {
+        let today = Utc::now().date_naive();
+        let retired = AIModel { deprecation_date: Some(today), ..model("cheap-retired", vec![ModelCapability::ChatCompletion], Some(0.1)) };
+        let retiring = AIModel { deprecation_date: Some(today + Duration::days(30)), ..model("cheap-retiring", vec![ModelCapability::ChatCompletion], Some(0.5)) };
+        let provider = Listing(vec![
+            retired,
+            model("embedder", vec![ModelCapability::Embedding], Some(0.01)),
+            retiring,
+            model("current", vec![ModelCapability::ChatCompletion], Some(1.0)),
+        ]);
+
+        let resolved = ModelResolver::default().resolve(&provider, None).await.unwrap();
+        assert_eq!(resolved.model.id, "cheap-retiring");
+        assert_eq!(resolved.source, ModelSource::CatalogFallback);
+
+        // A retired model can still be asked for by name
+        let resolved = ModelResolver::default().resolve(&provider, Some("cheap-retired")).await.unwrap();
+        assert_eq!(resolved.source, ModelSource::Explicit);
+
+        let error = ModelResolver::default().resolve(&Listing(vec![model("embedder", vec![ModelCapability::Embedding], None)]), None).await.unwrap_err();
+        assert_eq!(error.kind(), AIErrorKind::ModelNotFound);
+    }
+}
@@ -7,6 +7,87 @@ use crate::ai::models::ChatCompletionResponse;
 use crate::ai::models::ChatCompletionChunk;
 use crate::ai::models::Embedding;
 use crate::ai::models::EmbeddingRequest;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use futures::Stream;
+
+/// The stream every streaming provider method returns; boxed so the traits work as `dyn` objects
+pub type ChatCompletionStream = Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send>>;
+
+/// What went wrong, whichever provider reported it, so callers can react to the kind of failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AIErrorKind {
+    /// Missing, invalid or unauthorized credentials
+    Auth,
+    /// Too many requests for now; waiting helps
+    RateLimited,
+    /// Out of credit or over a hard quota; waiting doesn't help
+    QuotaExhausted,
+    /// The prompt plus the requested output doesn't fit the model's context window
+    ContextLengthExceeded,
+    /// The provider's safety filters blocked the prompt or the answer
+    ContentFiltered,
+    ModelNotFound,
+    InvalidRequest,
+    /// The provider failed or is overloaded
+    Server,
+    /// The provider couldn't be reached, or the connection dropped
+    Network,
+    Timeout,
+    /// The caller cancelled the request
+    Cancelled,
+    Other,
+}
+
+/// Everything a provider told us about a failed request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderErrorDetails {
+    pub kind: AIErrorKind,
+    pub provider: String,
+    pub message: String,
+    /// HTTP status, when the failure came with a response
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// The provider's machine readable code, e.g. "context_length_exceeded"
+    #[serde(default)]
+    pub code: Option<String>,
+    /// The provider's error type or status name, e.g. "rate_limit_error" or "RESOURCE_EXHAUSTED"
+    #[serde(default)]
+    pub error_type: Option<String>,
+    /// The provider's id for the request, for support tickets
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+impl ProviderErrorDetails {
+    pub fn new(kind: AIErrorKind, provider: &str, message: impl Into<String>) -> Self {
+        ProviderErrorDetails {
+            kind,
+            provider: provider.to_string(),
+            message: message.into(),
+            status: None,
+            code: None,
+            error_type: None,
+            request_id: None,
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderErrorDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.provider, self.message)?;
+        if let Some(status) = self.status {
+            write!(f, " (HTTP {})", status)?;
+        }
+        if let Some(code) = self.code.as_ref().or(self.error_type.as_ref()) {
+            write!(f, " [{}]", code)?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request {})", request_id)?;
+        }
+        Ok(())
+    }
+}
 
 /// Represents any error that can occur when interacting with AI providers
 #[derive(Debug, thiserror::Error)]
@@ -35,13 +116,112 @@ pub enum AIProviderError {
     #[error("Model not found error: {0}")]
     ModelNotFound(String),
     
-    #[error("Deserialization error: {0}")]  
+    #[error("Deserialization error: {0}")]
     DeserializationError(String),
+
+    #[error("Request cancelled")]
+    Cancelled,
+
+    /// A failure reported by a provider, classified. Boxed to keep `Result`s small.
+    #[error("{0}")]
+    Provider(Box<ProviderErrorDetails>),
+}
+
+impl AIProviderError {
+    /// A classified error from `provider`
+    pub fn provider(kind: AIErrorKind, provider: &str, message: impl Into<String>) -> Self {
+        AIProviderError::Provider(Box::new(ProviderErrorDetails::new(kind, provider, message)))
+    }
+
+    /// The kind of failure. Errors without details are classified from their variant.
+    pub fn kind(&self) -> AIErrorKind {
+        match self {
+            AIProviderError::Provider(details) => details.kind,
+            AIProviderError::RateLimitExceeded => AIErrorKind::RateLimited,
+            AIProviderError::AuthError(_) => AIErrorKind::Auth,
+            AIProviderError::ModelNotAvailable(_) | AIProviderError::ModelNotFound(_) => AIErrorKind::ModelNotFound,
+            AIProviderError::InvalidRequest(_) => AIErrorKind::InvalidRequest,
+            AIProviderError::Cancelled => AIErrorKind::Cancelled,
+            _ => AIErrorKind::Other,
+        }
+    }
+
+    /// Provider, status, code and request id, when the provider reported them
+    pub fn details(&self) -> Option<&ProviderErrorDetails> {
+        match self {
+            AIProviderError::Provider(details) => Some(details.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Whether the same request might succeed if sent again, or sent somewhere else
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // Unclassified API errors may be anything, so they keep getting the benefit of the doubt
+            AIProviderError::APIError(_) => true,
+            _ => matches!(
+                self.kind(),
+                AIErrorKind::RateLimited | AIErrorKind::Server | AIErrorKind::Network | AIErrorKind::Timeout
+            ),
+        }
+    }
+
+    /// Whether the credentials need fixing before anything will work
+    pub fn is_auth(&self) -> bool {
+        self.kind() == AIErrorKind::Auth
+    }
+}
+
+/// How a provider answered a health check
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthStatus {
+    pub provider: String,
+    /// Whether the server answered at all. An auth error still counts: the server is there.
+    pub reachable: bool,
+    /// Round trip of the first probe request
+    pub latency_ms: Option<u64>,
+    pub version: Option<String>,
+    /// Models the server offers
+    pub models: Vec<String>,
+    /// Models loaded into memory, for servers that say
+    pub loaded_models: Option<Vec<String>>,
+    pub error: Option<String>,
+    pub error_kind: Option<AIErrorKind>,
+}
+
+impl HealthStatus {
+    /// The status for a `list_models` call that took `latency`
+    pub fn from_models(provider: String, latency: Duration, models: Result<Vec<AIModel>, AIProviderError>) -> Self {
+        match models {
+            Ok(models) => HealthStatus {
+                provider,
+                reachable: true,
+                latency_ms: Some(latency.as_millis() as u64),
+                models: models.into_iter().map(|model| model.id).collect(),
+                ..Default::default()
+            },
+            Err(error) => HealthStatus::failed(provider, latency, error),
+        }
+    }
+
+    /// The status for a probe that failed with `error` after `latency`
+    pub fn failed(provider: String, latency: Duration, error: AIProviderError) -> Self {
+        let kind = error.kind();
+        let reachable = !matches!(kind, AIErrorKind::Network | AIErrorKind::Timeout);
+        HealthStatus {
+            provider,
+            reachable,
+            latency_ms: reachable.then_some(latency.as_millis() as u64),
+            error: Some(error.to_string()),
+            error_kind: Some(kind),
+            ..Default::default()
+        }
+    }
 }
 
 /// Core trait for retrieving models
 #[async_trait]
-pub trait ModelProvider {
+pub trait ModelProvider: Send + Sync {
     /// List available models
     async fn list_models(&self) -> Result<Vec<AIModel>, AIProviderError>;
     
@@ -54,11 +234,24 @@ pub trait ModelProvider {
     fn get_provider_name(&self) -> String;
 
     fn set_preferred_inference_model(&mut self, model_name: String) -> Result<(), AIProviderError>;
+
+    /// The model set with `set_preferred_inference_model`, if any
+    fn get_preferred_model_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Check the provider answers, by listing its models. Servers that report their version or
+    /// which models are loaded override this.
+    async fn health_check(&self) -> HealthStatus {
+        let started = Instant::now();
+        let models = self.list_models().await;
+        HealthStatus::from_models(self.get_provider_name(), started.elapsed(), models)
+    }
 }
 
 /// Core trait for chat completions
 #[async_trait]
-pub trait ChatCompletionProvider {
+pub trait ChatCompletionProvider: Send + Sync {
     /// Generate chat completion
     async fn create_chat_completion(
         &self, 
@@ -69,12 +262,12 @@ pub trait ChatCompletionProvider {
     async fn create_streaming_chat_completion(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<impl futures::Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send, AIProviderError>;
+    ) -> Result<ChatCompletionStream, AIProviderError>;
 }
 
 /// Core trait for embeddings
 #[async_trait]
-pub trait EmbeddingProvider {
+pub trait EmbeddingProvider: Send + Sync {
     /// Generate embeddings for text
     async fn create_embeddings(
         &self,
@@ -85,14 +278,26 @@ pub trait EmbeddingProvider {
 }
 
 /// Trait to get the preferred embedding model
-pub trait PreferredEmbeddingModel {
+pub trait PreferredEmbeddingModel: Send + Sync {
     fn get_preferred_embedding_model(&self) -> String;
 }
 
+/// Trait for diffusion models that refine the whole answer in place while streaming
 #[async_trait]
-pub trait DiffusingProvider {
+pub trait DiffusingProvider: Send + Sync {
+    /// Stream refinements of a chat completion. Unlike `create_streaming_chat_completion`,
+    /// each chunk's content is the full text so far and replaces the previous one.
     async fn create_diffusing_stream(
         &self,
         request: &ChatCompletionRequest,
-    ) -> Result<impl futures::Stream<Item = Result<ChatCompletionChunk, AIProviderError>> + Send, AIProviderError>;
-}
\ No newline at end of file
+    ) -> Result<ChatCompletionStream, AIProviderError>;
+}
+
+/// A complete provider: models, chat, embeddings. `Arc<dyn AIProvider>` is how providers
+/// from outside this crate are plugged in (see `Provider::Custom`).
+pub trait AIProvider: ModelProvider + ChatCompletionProvider + EmbeddingProvider + PreferredEmbeddingModel + std::fmt::Debug {
+    /// This provider's diffusing streams, if it has them
+    fn as_diffusing(&self) -> Option<&dyn DiffusingProvider> {
+        None
+    }
+}
@@ -4,6 +4,7 @@
 use tauri::{generate_handler, Runtime, Builder, Emitter, AppHandle, Manager, Window, State, WebviewWindowBuilder, WebviewWindow, WebviewUrl};
 use std::fs;
 use std::path::PathBuf;
+use std::collections::HashMap;
 use tauri::path::{BaseDirectory};
 use serde_json::json;
 //mod preferences;
@@ -25,9 +26,13 @@ mod logger;
 use logger::NewLogger;
 pub mod ai;
 use crate::ai::{
-    providers::{Provider, ProviderType, create_provider},
+    providers::{Provider, OllamaProvider, LMStudioProvider, OllamaOptions, OllamaPullProgress, OllamaDiskUsage, OpenAICompatibleConfig, AzureOpenAIConfig, CassetteProvider, CassetteMode, ProviderRegistry, ProviderDescriptor, ConfigFieldKind, FallbackProvider, FallbackLink, FallbackLinkConfig, RetryPolicy, RetryEvent, ConnectionConfig, DiscoveredServer, LocalServerKind},
     models::{ChatCompletionRequest, ChatMessage, MessageRole},
-    traits::{ChatCompletionProvider, ModelProvider},
+    traits::{ChatCompletionProvider, ModelProvider, AIProviderError, AIErrorKind, ProviderErrorDetails, HealthStatus},
+    cancellation::InFlightRequests,
+    catalog::{ModelCatalog, CatalogEntry, CatalogFile},
+    model_cache::{ModelCache, ProviderModels, ListedModel},
+    resolver::{ModelResolver, ModelPreferences, ResolvedModel},
 };
 pub fn emit_console_message(app_handle: &AppHandle, level: &str, message: &str) {
     let payload = serde_json::json!({ "level": level, "message": message });
@@ -35,27 +40,10 @@ pub fn emit_console_message(app_handle: &AppHandle, level: &str, message: &str)
     log::debug!("Emitting console message: {} - {}", level, message);
 }
 
-#[tauri::command]
-async fn list_openai_models(api_key: String) -> Result<Vec<String>, String> {
-    let client = reqwest::Client::new();
-    let res = client
-    .get("https://api.openai.com/v1/models")
-    .header(AUTHORIZATION, format!("Bearer {}", api_key))
-    .send()
-    .await
-    .map_err(|e| e.to_string())?;
-    let json: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
-    let models = json["data"]
-    .as_array()
-    .ok_or("No models found")?
-    .iter()
-    .filter_map(|m| m["id"].as_str().map(|s| s.to_string()))
-    .collect();
-    Ok(models)
-}
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use serde::{Deserialize, Serialize};
-use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+use futures::StreamExt;
 
 #[tauri::command]
 async fn greet(name: &str, 
@@ -72,6 +60,62 @@ async fn greet(name: &str,
         pub text: String,
     }
     
+    #[derive(Serialize)]
+    pub struct TransformResponse {
+        pub text: String,
+        // The provider and model that answered, which differ from the request when a fallback was used
+        pub provider: String,
+        pub model: String,
+        // That model as the provider describes it, and whether it was requested, preferred or picked
+        pub resolved_model: ResolvedModel,
+        // Id the transform ran under, for cancel_transform
+        pub request_id: String,
+    }
+    
+    // Health of one configured provider, as reported by check_providers_health
+    #[derive(Serialize)]
+    pub struct ProviderHealth {
+        pub provider_id: String,
+        // Name of the saved endpoint, for OpenAI compatible providers
+        pub endpoint: Option<String>,
+        pub health: HealthStatus,
+    }
+    
+    // Progress of pull_ollama_model, emitted as "ollama-pull-progress"
+    #[derive(Clone, Serialize)]
+    pub struct OllamaPullEvent {
+        pub model_name: String,
+        #[serde(flatten)]
+        pub progress: OllamaPullProgress,
+    }
+    
+    // A failed AI command. Provider failures carry their classification so the UI can react to
+    // the kind of failure (fix the API key, shorten the text, wait) instead of just printing it.
+    #[derive(Debug, Serialize)]
+    pub struct CommandError {
+        pub message: String,
+        pub kind: AIErrorKind,
+        pub retryable: bool,
+        pub details: Option<ProviderErrorDetails>,
+    }
+    
+    impl From<String> for CommandError {
+        fn from(message: String) -> Self {
+            CommandError { message, kind: AIErrorKind::Other, retryable: false, details: None }
+        }
+    }
+    
+    impl From<AIProviderError> for CommandError {
+        fn from(error: AIProviderError) -> Self {
+            CommandError {
+                message: error.to_string(),
+                kind: error.kind(),
+                retryable: error.is_retryable(),
+                details: error.details().cloned(),
+            }
+        }
+    }
+    
     #[derive(Serialize)]
     struct OpenAIRequest {
         model: String,
@@ -88,28 +132,9 @@ async fn greet(name: &str,
     // Replace the save_api_key function
     #[tauri::command]
     async fn save_api_key(app_handle: tauri::AppHandle, api_key: String) -> Result<(), String> {
-        // Get the app's data directory
-        let mut config_path: PathBuf = app_handle.path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-        
-        log::debug!("App data directory: {:?}", config_path);
-        
-        // Add config subdirectory
-        config_path.push("config");
-        
-        // Create the directory if it doesn't exist
-        fs::create_dir_all(&config_path).map_err(|e| format!("Failed to create config directory: {}", e))?;
-        
-        // Add credentials.json file
-        config_path.push("credentials.json");
-        
-        // Create JSON with the API key
-        let contents = json!({ "openai_api_key": api_key }).to_string();
-        
-        // Write to file
-        fs::write(&config_path, contents).map_err(|e| format!("Failed to write credentials file: {}", e))?;
+        write_credential(&app_handle, "openai_api_key", &api_key)?;
         
+        invalidate_model_cache(&app_handle);
         emit_console_message(&app_handle, "info", "API key saved successfully");
         Ok(())
     }
@@ -149,19 +174,29 @@ async fn greet(name: &str,
     
     // Function to get API key from various sources with priority
     pub fn get_openai_api_key(app_handle: &AppHandle) -> Result<String, String> {
+        get_provider_api_key(app_handle, "OPENAI_API_KEY", "openai_api_key", "OpenAI")
+    }
+    
+    // Look up a provider's API key: environment variable, then .env file, then the saved credentials file
+    pub fn get_provider_api_key(
+        app_handle: &AppHandle,
+        env_var: &str,
+        config_key: &str,
+        provider_label: &str,
+    ) -> Result<String, String> {
         // Try environment variable first (highest priority)
-        if let Ok(key) = env::var("OPENAI_API_KEY") {
+        if let Ok(key) = env::var(env_var) {
             if !key.is_empty() {
-                emit_console_message(app_handle, "debug", "Using API key from environment variable");
+                emit_console_message(app_handle, "debug", &format!("Using {} API key from environment variable", provider_label));
                 return Ok(key);
             }
         }
         
         // Try loading from .env file if available
         if dotenv().is_ok() {
-            if let Ok(key) = env::var("OPENAI_API_KEY") {
+            if let Ok(key) = env::var(env_var) {
                 if !key.is_empty() {
-                    emit_console_message(app_handle, "debug", "Using API key from .env file");
+                    emit_console_message(app_handle, "debug", &format!("Using {} API key from .env file", provider_label));
                     return Ok(key);
                 }
             }
@@ -182,16 +217,998 @@ async fn greet(name: &str,
             let json: serde_json::Value = serde_json::from_str(&contents)
             .map_err(|e| format!("Failed to parse credentials file: {}", e))?;
             
-            if let Some(key) = json["openai_api_key"].as_str() {
+            if let Some(key) = json[config_key].as_str() {
                 if !key.is_empty() {
-                    emit_console_message(app_handle, "debug", "Using API key from config file");
+                    emit_console_message(app_handle, "debug", &format!("Using {} API key from config file", provider_label));
                     return Ok(key.to_string());
                 }
             }
         }
         
         // No API key found
-        Err("OpenAI API key not found. Please set your API key in settings".to_string())
+        Err(format!("{} API key not found. Please set your API key in settings", provider_label))
+    }
+    
+    // Save one key in credentials.json, keeping whatever other keys are already saved
+    fn write_credential(app_handle: &AppHandle, credential_key: &str, api_key: &str) -> Result<(), String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        fs::create_dir_all(&config_path).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        config_path.push("credentials.json");
+        
+        // Keep whatever other keys are already saved
+        let mut json: serde_json::Value = if config_path.exists() {
+            let contents = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read credentials file: {}", e))?;
+            serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse credentials file: {}", e))?
+        } else {
+            json!({})
+        };
+        
+        json[credential_key] = json!(api_key);
+        
+        fs::write(&config_path, json.to_string()).map_err(|e| format!("Failed to write credentials file: {}", e))
+    }
+    
+    // Save an API key for any provider alongside the others in credentials.json, under the
+    // credential key its config schema names
+    #[tauri::command]
+    async fn save_provider_api_key(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        provider: String,
+        api_key: String,
+    ) -> Result<(), String> {
+        let descriptor = registry.descriptor(&provider).map_err(|e| e.to_string())?;
+        let credential_key = descriptor.config_schema.iter()
+            .find_map(|field| field.credential_key.clone())
+            .ok_or_else(|| format!("{} doesn't keep an API key with the others", descriptor.display_name))?;
+        
+        write_credential(&app_handle, &credential_key, &api_key)?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("{} API key saved successfully", descriptor.display_name));
+        Ok(())
+    }
+    
+    // Path to the saved per-model Ollama option profiles
+    fn ollama_model_options_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("ollama_model_options.json");
+        Ok(config_path)
+    }
+    
+    // Read every saved Ollama option profile, keyed by model name
+    pub fn read_ollama_model_options(app_handle: &AppHandle) -> Result<HashMap<String, OllamaOptions>, String> {
+        let config_path = ollama_model_options_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(HashMap::new());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read Ollama model options file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse Ollama model options file: {}", e))
+    }
+    
+    fn write_ollama_model_options(app_handle: &AppHandle, model_options: &HashMap<String, OllamaOptions>) -> Result<(), String> {
+        let config_path = ollama_model_options_path(app_handle)?;
+        
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(model_options)
+        .map_err(|e| format!("Failed to serialize Ollama model options: {}", e))?;
+        
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write Ollama model options file: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn load_ollama_model_options(app_handle: tauri::AppHandle) -> Result<HashMap<String, OllamaOptions>, String> {
+        read_ollama_model_options(&app_handle)
+    }
+    
+    #[tauri::command]
+    async fn save_ollama_model_options(app_handle: tauri::AppHandle, model_name: String, options: OllamaOptions) -> Result<(), String> {
+        let mut model_options = read_ollama_model_options(&app_handle)?;
+        model_options.insert(model_name.clone(), options);
+        write_ollama_model_options(&app_handle, &model_options)?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Saved Ollama options for {}", model_name));
+        Ok(())
+    }
+    
+    #[tauri::command]
+    async fn delete_ollama_model_options(app_handle: tauri::AppHandle, model_name: String) -> Result<(), String> {
+        let mut model_options = read_ollama_model_options(&app_handle)?;
+        if model_options.remove(&model_name).is_some() {
+            write_ollama_model_options(&app_handle, &model_options)?;
+            invalidate_model_cache(&app_handle);
+            emit_console_message(&app_handle, "info", &format!("Removed Ollama options for {}", model_name));
+        }
+        Ok(())
+    }
+    
+    // Path to the saved OpenAI compatible endpoints
+    fn openai_compatible_endpoints_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("openai_compatible_endpoints.json");
+        Ok(config_path)
+    }
+    
+    // Read every saved OpenAI compatible endpoint, keys included
+    pub fn read_openai_compatible_endpoints(app_handle: &AppHandle) -> Result<Vec<OpenAICompatibleConfig>, String> {
+        let config_path = openai_compatible_endpoints_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read endpoints file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse endpoints file: {}", e))
+    }
+    
+    fn write_openai_compatible_endpoints(app_handle: &AppHandle, endpoints: &[OpenAICompatibleConfig]) -> Result<(), String> {
+        let config_path = openai_compatible_endpoints_path(app_handle)?;
+        
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(endpoints)
+        .map_err(|e| format!("Failed to serialize endpoints: {}", e))?;
+        
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write endpoints file: {}", e))
+    }
+    
+    // List saved endpoints. API keys are left out, like get_api_key does for OpenAI.
+    #[tauri::command]
+    async fn list_openai_compatible_endpoints(app_handle: tauri::AppHandle) -> Result<Vec<OpenAICompatibleConfig>, String> {
+        let endpoints = read_openai_compatible_endpoints(&app_handle)?;
+        Ok(endpoints.into_iter()
+            .map(|endpoint| OpenAICompatibleConfig { api_key: None, ..endpoint })
+            .collect())
+    }
+    
+    // Add or replace an endpoint by name. Leaving `api_key` out keeps the one already saved.
+    #[tauri::command]
+    async fn save_openai_compatible_endpoint(app_handle: tauri::AppHandle, endpoint: OpenAICompatibleConfig) -> Result<(), String> {
+        if endpoint.name.trim().is_empty() || endpoint.base_url.trim().is_empty() {
+            return Err("An endpoint needs a name and a base URL".to_string());
+        }
+        
+        let mut endpoints = read_openai_compatible_endpoints(&app_handle)?;
+        let mut endpoint = endpoint;
+        
+        if let Some(existing) = endpoints.iter().position(|e| e.name == endpoint.name) {
+            let existing = endpoints.remove(existing);
+            if endpoint.api_key.is_none() {
+                endpoint.api_key = existing.api_key;
+            }
+        }
+        
+        let name = endpoint.name.clone();
+        endpoints.push(endpoint);
+        write_openai_compatible_endpoints(&app_handle, &endpoints)?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Saved endpoint {}", name));
+        Ok(())
+    }
+    
+    #[tauri::command]
+    async fn delete_openai_compatible_endpoint(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+        let mut endpoints = read_openai_compatible_endpoints(&app_handle)?;
+        let before = endpoints.len();
+        endpoints.retain(|e| e.name != name);
+        
+        if endpoints.len() != before {
+            write_openai_compatible_endpoints(&app_handle, &endpoints)?;
+            invalidate_model_cache(&app_handle);
+            emit_console_message(&app_handle, "info", &format!("Removed endpoint {}", name));
+        }
+        Ok(())
+    }
+    
+    // Path to the saved Azure OpenAI resource settings
+    fn azure_openai_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("azure_openai.json");
+        Ok(config_path)
+    }
+    
+    // Read the Azure OpenAI settings, falling back to the usual key sources when none is saved with them
+    pub fn read_azure_openai_config(app_handle: &AppHandle) -> Result<AzureOpenAIConfig, String> {
+        let config_path = azure_openai_config_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Err("Azure OpenAI is not configured. Please set the endpoint and deployments in settings".to_string());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read Azure OpenAI config file: {}", e))?;
+        
+        let mut azure_config: AzureOpenAIConfig = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse Azure OpenAI config file: {}", e))?;
+        
+        if azure_config.api_key.is_empty() {
+            azure_config.api_key = get_provider_api_key(app_handle, "AZURE_OPENAI_API_KEY", "azure_openai_api_key", "Azure OpenAI")?;
+        }
+        
+        Ok(azure_config)
+    }
+    
+    // The saved Azure OpenAI settings, without the API key
+    #[tauri::command]
+    async fn load_azure_openai_config(app_handle: tauri::AppHandle) -> Result<AzureOpenAIConfig, String> {
+        let config_path = azure_openai_config_path(&app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(AzureOpenAIConfig::default());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read Azure OpenAI config file: {}", e))?;
+        
+        let azure_config: AzureOpenAIConfig = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse Azure OpenAI config file: {}", e))?;
+        
+        Ok(AzureOpenAIConfig { api_key: String::new(), ..azure_config })
+    }
+    
+    // Save the Azure OpenAI settings. An empty `api_key` keeps the one already saved.
+    #[tauri::command]
+    async fn save_azure_openai_config(app_handle: tauri::AppHandle, config: AzureOpenAIConfig) -> Result<(), String> {
+        let config_path = azure_openai_config_path(&app_handle)?;
+        let mut config = config;
+        
+        if config.api_key.is_empty() && config_path.exists() {
+            let contents = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read Azure OpenAI config file: {}", e))?;
+            if let Ok(existing) = serde_json::from_str::<AzureOpenAIConfig>(&contents) {
+                config.api_key = existing.api_key;
+            }
+        }
+        
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize Azure OpenAI config: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write Azure OpenAI config file: {}", e))?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", "Azure OpenAI settings saved successfully");
+        Ok(())
+    }
+    
+    // Mock fixtures come from MOCK_FIXTURES_PATH, else config/mock_fixtures.yaml; with neither the mock echoes prompts
+    pub fn mock_fixtures_path(app_handle: &AppHandle) -> Result<Option<PathBuf>, String> {
+        if let Ok(path) = env::var("MOCK_FIXTURES_PATH") {
+            if !path.is_empty() {
+                return Ok(Some(PathBuf::from(path)));
+            }
+        }
+        
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("mock_fixtures.yaml");
+        
+        Ok(config_path.exists().then_some(config_path))
+    }
+    
+    // Providers whose settings have their own file and commands, rather than provider_settings.json
+    const SEPARATELY_CONFIGURED_PROVIDERS: [&str; 3] = ["OpenAICompatible", "AzureOpenAI", "Mock"];
+    
+    fn provider_settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("provider_settings.json");
+        Ok(config_path)
+    }
+    
+    // Read every provider's saved settings, keyed by provider id
+    pub fn read_provider_settings(app_handle: &AppHandle) -> Result<HashMap<String, serde_json::Map<String, serde_json::Value>>, String> {
+        let config_path = provider_settings_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(HashMap::new());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read provider settings file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse provider settings file: {}", e))
+    }
+    
+    // Replace one provider's saved settings; empty settings remove them
+    fn write_provider_settings(app_handle: &AppHandle, provider_id: &str, settings: serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+        let mut all_settings = read_provider_settings(app_handle)?;
+        if settings.is_empty() {
+            all_settings.remove(provider_id);
+        } else {
+            all_settings.insert(provider_id.to_string(), settings);
+        }
+        
+        let config_path = provider_settings_path(app_handle)?;
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&all_settings)
+        .map_err(|e| format!("Failed to serialize provider settings: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write provider settings file: {}", e))?;
+        
+        invalidate_model_cache(app_handle);
+        Ok(())
+    }
+    
+    // A provider's saved settings, e.g. the base_url of LM Studio or Ollama
+    #[tauri::command]
+    async fn load_provider_settings(app_handle: tauri::AppHandle, provider: String) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+        Ok(read_provider_settings(&app_handle)?.remove(&provider).unwrap_or_default())
+    }
+    
+    // Save a provider's settings, replacing those saved before. Only fields of its config schema
+    // are taken; API keys are saved with save_provider_api_key.
+    #[tauri::command]
+    async fn save_provider_settings(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        provider: String,
+        settings: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(), String> {
+        let descriptor = registry.descriptor(&provider).map_err(|e| e.to_string())?;
+        if SEPARATELY_CONFIGURED_PROVIDERS.contains(&descriptor.id.as_str()) {
+            return Err(format!("{} settings are saved separately", descriptor.display_name));
+        }
+        
+        for name in settings.keys() {
+            match descriptor.config_schema.iter().find(|field| &field.name == name) {
+                None => return Err(format!("{} has no setting named {}", descriptor.display_name, name)),
+                Some(field) if field.kind == ConfigFieldKind::ApiKey => {
+                    return Err(format!("Save the {} API key with the other API keys", descriptor.display_name));
+                },
+                Some(_) => {},
+            }
+        }
+        
+        write_provider_settings(&app_handle, &descriptor.id, settings)?;
+        emit_console_message(&app_handle, "info", &format!("{} settings saved successfully", descriptor.display_name));
+        Ok(())
+    }
+    
+    // Assemble a provider's settings: saved settings first, then API keys its schema asks for
+    fn provider_config(app_handle: &AppHandle, descriptor: &ProviderDescriptor, endpoint: Option<&str>) -> Result<serde_json::Value, String> {
+        let mut config = match descriptor.id.as_str() {
+            "OpenAICompatible" => {
+                let endpoint_name = endpoint
+                    .ok_or("Choose a saved endpoint to use an OpenAI compatible provider")?;
+                let endpoint_config = read_openai_compatible_endpoints(app_handle)?
+                    .into_iter()
+                    .find(|e| e.name == endpoint_name)
+                    .ok_or_else(|| format!("No saved endpoint named {}", endpoint_name))?;
+                json!(endpoint_config)
+            },
+            "AzureOpenAI" => json!(read_azure_openai_config(app_handle)?),
+            "Mock" => json!({ "fixtures_path": mock_fixtures_path(app_handle)? }),
+            _ => json!(read_provider_settings(app_handle)?.remove(&descriptor.id).unwrap_or_default()),
+        };
+        
+        for field in &descriptor.config_schema {
+            let (Some(env_var), Some(credential_key)) = (&field.env_var, &field.credential_key) else {
+                continue;
+            };
+            if config[&field.name].as_str().is_some_and(|value| !value.is_empty()) {
+                continue;
+            }
+            match get_provider_api_key(app_handle, env_var, credential_key, &descriptor.display_name) {
+                Ok(key) => config[&field.name] = json!(key),
+                Err(e) if field.required => return Err(e),
+                Err(_) => {},
+            }
+        }
+        
+        Ok(config)
+    }
+    
+    // Every provider transform_text can use, with the settings each one takes
+    #[tauri::command]
+    async fn list_providers(registry: State<'_, ProviderRegistry>) -> Result<Vec<ProviderDescriptor>, String> {
+        Ok(registry.descriptors())
+    }
+    
+    // Build a registered provider with its saved settings, option profiles and cassette
+    fn build_provider(app_handle: &AppHandle, registry: &ProviderRegistry, provider_id: &str, endpoint: Option<&str>) -> Result<Provider, String> {
+        let descriptor = registry.descriptor(provider_id).map_err(|e| e.to_string())?;
+        
+        // Get provider config (API keys, URLs, saved settings)
+        let config = provider_config(app_handle, descriptor, endpoint)?;
+        
+        let mut provider = registry.create(provider_id, &config).map_err(|e| e.to_string())?;
+        
+        // Use the model the user prefers with this provider when a request doesn't name one
+        if let Some(model_name) = read_model_preferences(app_handle)?.preferred_for(provider_id) {
+            provider.set_preferred_inference_model(model_name.to_string()).map_err(|e| e.to_string())?;
+        }
+        
+        // Apply the user's saved per-model option profiles
+        if let Provider::Ollama(ollama_provider) = &mut provider {
+            ollama_provider.set_model_options(read_ollama_model_options(app_handle)?);
+        }
+        
+        // Apply the saved timeouts, proxy, CA bundle and user-agent
+        let connection = read_connection_config(app_handle)?.settings_for(provider_id, endpoint);
+        provider.set_connection_settings(connection).map_err(|e| e.to_string())?;
+        
+        // Show retries in the console so a slow answer isn't mistaken for a hang
+        let console = app_handle.clone();
+        provider.set_retry_policy(RetryPolicy::default().with_observer(move |event: &RetryEvent| {
+            emit_console_message(&console, "warn", &format!(
+                "{}: retrying (attempt {} of {}) in {} ms after {}",
+                event.provider, event.attempt, event.max_attempts, event.delay_ms, event.reason
+            ));
+        }));
+        
+        // Record or replay this session's exchanges when a cassette is configured
+        if let Ok(cassette_path) = env::var("CASSETTE_PATH") {
+            let mode_name = env::var("CASSETTE_MODE").unwrap_or_else(|_| "auto".to_string());
+            let mode = CassetteMode::from_name(&mode_name)
+                .ok_or_else(|| format!("Unknown CASSETTE_MODE {}; use record, replay or auto", mode_name))?;
+            let cassette = CassetteProvider::new(provider, &cassette_path, mode)
+                .map_err(|e| e.to_string())?;
+            emit_console_message(app_handle, "info", &format!("Using cassette {} ({:?})", cassette_path, mode));
+            provider = Provider::Cassette(cassette);
+        }
+        
+        Ok(provider)
+    }
+    
+    // Probe a provider with its saved settings. Retries are off and timeouts cut to a few seconds,
+    // so a dead or stuck server shows up quickly.
+    async fn probe_provider(app_handle: &AppHandle, registry: &ProviderRegistry, provider_id: &str, endpoint: Option<&str>) -> Result<HealthStatus, String> {
+        let mut provider = build_provider(app_handle, registry, provider_id, endpoint)?;
+        provider.set_retry_policy(RetryPolicy::none());
+        let connection = read_connection_config(app_handle)?.settings_for(provider_id, endpoint);
+        provider.set_connection_settings(connection.for_probe()).map_err(|e| e.to_string())?;
+        
+        let health = provider.health_check().await;
+        if !health.reachable {
+            let error = health.error.as_deref().unwrap_or("no answer");
+            emit_console_message(app_handle, "warn", &format!("{} is not reachable: {}", provider_id, error));
+        }
+        Ok(health)
+    }
+    
+    #[tauri::command]
+    async fn check_provider_health(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        provider_type: String,
+        endpoint: Option<String>,
+    ) -> Result<HealthStatus, String> {
+        probe_provider(&app_handle, &registry, &provider_type, endpoint.as_deref()).await
+    }
+    
+    // Every registered provider, and every saved OpenAI compatible endpoint, as (provider id,
+    // endpoint) pairs. The mock is left out.
+    fn configured_provider_targets(app_handle: &AppHandle, registry: &ProviderRegistry) -> Result<Vec<(String, Option<String>)>, String> {
+        let mut targets: Vec<(String, Option<String>)> = registry.descriptors().into_iter()
+            .filter(|descriptor| descriptor.id != "Mock" && descriptor.id != "OpenAICompatible")
+            .map(|descriptor| (descriptor.id, None))
+            .collect();
+        for endpoint in read_openai_compatible_endpoints(app_handle)? {
+            targets.push(("OpenAICompatible".to_string(), Some(endpoint.name)));
+        }
+        Ok(targets)
+    }
+    
+    // Probe every configured provider at once. Providers missing settings or an API key are
+    // left out; the mock is always healthy so it's skipped too.
+    #[tauri::command]
+    async fn check_providers_health(app_handle: tauri::AppHandle, registry: State<'_, ProviderRegistry>) -> Result<Vec<ProviderHealth>, String> {
+        let targets = configured_provider_targets(&app_handle, &registry)?;
+        
+        let probes = targets.into_iter().map(|(provider_id, endpoint)| {
+            let app_handle = &app_handle;
+            let registry = &registry;
+            async move {
+                let health = probe_provider(app_handle, registry, &provider_id, endpoint.as_deref()).await.ok()?;
+                Some(ProviderHealth { provider_id, endpoint, health })
+            }
+        });
+        Ok(futures::future::join_all(probes).await.into_iter().flatten().collect())
+    }
+    
+    // Look for LM Studio, Ollama, llama.cpp and vLLM on their default localhost ports
+    #[tauri::command]
+    async fn discover_local_servers(app_handle: tauri::AppHandle) -> Result<Vec<DiscoveredServer>, String> {
+        let connection = read_connection_config(&app_handle)?.defaults;
+        let servers = ai::providers::discover_local_servers(&connection).await;
+        
+        for server in servers.iter().filter(|server| server.health.reachable) {
+            emit_console_message(&app_handle, "info", &format!(
+                "Found {} at {} ({} models)",
+                server.display_name, server.base_url, server.health.models.len()
+            ));
+        }
+        Ok(servers)
+    }
+    
+    // Use the local server of `kind` at `base_url`, e.g. one discover_local_servers found. LM
+    // Studio and Ollama get it as their base_url; llama.cpp and vLLM are saved as an OpenAI
+    // compatible endpoint named after the server. Returns that endpoint's name, to pass to
+    // transform_text along with the server's provider id.
+    #[tauri::command]
+    async fn use_local_server(app_handle: tauri::AppHandle, kind: LocalServerKind, base_url: String) -> Result<Option<String>, String> {
+        let connection = read_connection_config(&app_handle)?.settings_for(kind.provider_id(), None);
+        let server = ai::providers::probe_local_server(kind, &base_url, &connection).await;
+        if !server.health.reachable {
+            let error = server.health.error.as_deref().unwrap_or("no answer");
+            return Err(format!("{} is not reachable at {}: {}", kind.display_name(), base_url, error));
+        }
+        
+        let endpoint = match kind {
+            LocalServerKind::LMStudio | LocalServerKind::Ollama => {
+                let mut settings = read_provider_settings(&app_handle)?.remove(kind.provider_id()).unwrap_or_default();
+                settings.insert("base_url".to_string(), json!(base_url));
+                write_provider_settings(&app_handle, kind.provider_id(), settings)?;
+                None
+            },
+            LocalServerKind::LlamaCpp | LocalServerKind::VLLM => {
+                let name = kind.display_name().to_string();
+                let mut endpoints = read_openai_compatible_endpoints(&app_handle)?;
+                match endpoints.iter_mut().find(|endpoint| endpoint.name == name) {
+                    Some(endpoint) => endpoint.base_url = base_url.clone(),
+                    None => endpoints.push(OpenAICompatibleConfig {
+                        name: name.clone(),
+                        base_url: base_url.clone(),
+                        ..Default::default()
+                    }),
+                }
+                write_openai_compatible_endpoints(&app_handle, &endpoints)?;
+                invalidate_model_cache(&app_handle);
+                Some(name)
+            },
+        };
+        
+        emit_console_message(&app_handle, "info", &format!("Using {} at {}", kind.display_name(), base_url));
+        Ok(endpoint)
+    }
+    
+    // List every configured provider's models at once and store them in the cache. Providers
+    // missing settings or an API key are left out; retries are off so a dead server doesn't
+    // hold up the rest. The caller must have claimed the refresh, which is given up either way.
+    async fn refresh_model_cache(app_handle: &AppHandle) -> Result<Vec<ProviderModels>, String> {
+        let cache = app_handle.state::<ModelCache>();
+        let registry = app_handle.state::<ProviderRegistry>();
+        let targets = configured_provider_targets(app_handle, &registry)
+            .inspect_err(|_| cache.abort_refresh())?;
+        
+        let listings = targets.into_iter().map(|(provider_id, endpoint)| {
+            let registry = &registry;
+            async move {
+                let mut provider = build_provider(app_handle, registry, &provider_id, endpoint.as_deref()).ok()?;
+                provider.set_retry_policy(RetryPolicy::none());
+                Some(ProviderModels::from_result(&provider_id, endpoint.as_deref(), provider.list_models().await))
+            }
+        });
+        let listings = futures::future::join_all(listings).await.into_iter().flatten().collect();
+        Ok(cache.finish_refresh(listings))
+    }
+    
+    // Mark the cached model listing stale, after something that changes what's listed
+    fn invalidate_model_cache(app_handle: &AppHandle) {
+        app_handle.state::<ModelCache>().invalidate();
+    }
+    
+    // Every configured provider's models in one list, tagged with the provider id to use them
+    // with and whether that provider answered. The listing is cached: once it's older than the
+    // TTL it is still returned, and refreshed in the background, after which "models-updated"
+    // is emitted with the new list. `refresh` waits for a fresh listing instead, after any
+    // refresh already running.
+    #[tauri::command]
+    async fn list_all_models(app_handle: tauri::AppHandle, cache: State<'_, ModelCache>, refresh: Option<bool>) -> Result<Vec<ListedModel>, String> {
+        let listings = match cache.get() {
+            Some((listings, fresh)) if !refresh.unwrap_or(false) => {
+                if !fresh && cache.begin_refresh() {
+                    let app_handle = app_handle.clone();
+                    tauri::async_runtime::spawn(async move {
+                        match refresh_model_cache(&app_handle).await {
+                            Ok(listings) => {
+                                let models: Vec<ListedModel> = listings.iter().flat_map(ProviderModels::listed).collect();
+                                let _ = app_handle.emit("models-updated", models);
+                            },
+                            Err(e) => emit_console_message(&app_handle, "warn", &format!("Failed to refresh the model list: {}", e)),
+                        }
+                    });
+                }
+                listings
+            },
+            _ => {
+                cache.wait_to_refresh().await;
+                refresh_model_cache(&app_handle).await?
+            },
+        };
+        Ok(listings.iter().flat_map(ProviderModels::listed).collect())
+    }
+    
+    // The Ollama provider with its saved settings, for managing models rather than chatting
+    fn build_ollama_provider(app_handle: &AppHandle, registry: &ProviderRegistry) -> Result<OllamaProvider, String> {
+        match build_provider(app_handle, registry, "Ollama", None)? {
+            Provider::Ollama(provider) => Ok(provider),
+            _ => Err("Ollama models can't be managed while a cassette is in use".to_string()),
+        }
+    }
+    
+    // Id a pull runs under, for cancel_ollama_pull
+    fn ollama_pull_request_id(model_name: &str) -> String {
+        format!("ollama-pull:{}", model_name)
+    }
+    
+    // Download an Ollama model, e.g. "llama3.2:latest", emitting "ollama-pull-progress" events
+    // as it goes. The console hears about each step, e.g. each layer, but not every byte.
+    #[tauri::command]
+    async fn pull_ollama_model(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        in_flight: State<'_, InFlightRequests>,
+        model_name: String,
+    ) -> Result<(), CommandError> {
+        let in_flight_request = in_flight.start(&ollama_pull_request_id(&model_name))?;
+        let provider = build_ollama_provider(&app_handle, &registry)?;
+        emit_console_message(&app_handle, "info", &format!("Pulling Ollama model {}", model_name));
+        
+        let pull = async {
+            let mut progress = provider.pull_model(&model_name).await?;
+            let mut last_status = String::new();
+            while let Some(update) = progress.next().await {
+                let update = update?;
+                if update.status != last_status {
+                    emit_console_message(&app_handle, "info", &format!("{}: {}", model_name, update.status));
+                    last_status = update.status.clone();
+                }
+                let _ = app_handle.emit("ollama-pull-progress", OllamaPullEvent { model_name: model_name.clone(), progress: update });
+            }
+            Ok(())
+        };
+        
+        match in_flight_request.run(pull).await {
+            Ok(()) => {
+                invalidate_model_cache(&app_handle);
+                emit_console_message(&app_handle, "info", &format!("Pulled Ollama model {}", model_name));
+                Ok(())
+            },
+            Err(AIProviderError::Cancelled) => {
+                emit_console_message(&app_handle, "info", &format!("Cancelled pulling {}", model_name));
+                Err(AIProviderError::Cancelled.into())
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    // Stop a running pull_ollama_model. Returns false when that model isn't being pulled.
+    #[tauri::command]
+    fn cancel_ollama_pull(in_flight: State<'_, InFlightRequests>, model_name: String) -> bool {
+        in_flight.cancel(&ollama_pull_request_id(&model_name))
+    }
+    
+    #[tauri::command]
+    async fn delete_ollama_model(app_handle: tauri::AppHandle, registry: State<'_, ProviderRegistry>, model_name: String) -> Result<(), CommandError> {
+        let provider = build_ollama_provider(&app_handle, &registry)?;
+        provider.delete_model(&model_name).await?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Deleted Ollama model {}", model_name));
+        Ok(())
+    }
+    
+    // Install a copy of an Ollama model under a new name
+    #[tauri::command]
+    async fn copy_ollama_model(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        source: String,
+        destination: String,
+    ) -> Result<(), CommandError> {
+        let provider = build_ollama_provider(&app_handle, &registry)?;
+        provider.copy_model(&source, &destination).await?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Copied Ollama model {} to {}", source, destination));
+        Ok(())
+    }
+    
+    #[tauri::command]
+    async fn get_ollama_disk_usage(app_handle: tauri::AppHandle, registry: State<'_, ProviderRegistry>) -> Result<OllamaDiskUsage, CommandError> {
+        let provider = build_ollama_provider(&app_handle, &registry)?;
+        Ok(provider.disk_usage().await?)
+    }
+    
+    // The LM Studio provider with its saved settings, for loading and unloading models
+    fn build_lm_studio_provider(app_handle: &AppHandle, registry: &ProviderRegistry) -> Result<LMStudioProvider, String> {
+        match build_provider(app_handle, registry, "LMStudio", None)? {
+            Provider::LMStudio(provider) => Ok(provider),
+            _ => Err("LM Studio models can't be loaded while a cassette is in use".to_string()),
+        }
+    }
+    
+    // Load an LM Studio model so it's ready before the first transform, with the given context
+    // length or the model's default. Returns the id of the loaded instance, for unloading.
+    #[tauri::command]
+    async fn load_lm_studio_model(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        model_id: String,
+        context_length: Option<usize>,
+    ) -> Result<String, CommandError> {
+        let provider = build_lm_studio_provider(&app_handle, &registry)?;
+        emit_console_message(&app_handle, "info", &format!("Loading LM Studio model {}", model_id));
+        let instance_id = provider.load_model(&model_id, context_length).await?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Loaded LM Studio model {}", model_id));
+        Ok(instance_id)
+    }
+    
+    #[tauri::command]
+    async fn unload_lm_studio_model(app_handle: tauri::AppHandle, registry: State<'_, ProviderRegistry>, instance_id: String) -> Result<(), CommandError> {
+        let provider = build_lm_studio_provider(&app_handle, &registry)?;
+        provider.unload_model(&instance_id).await?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", &format!("Unloaded LM Studio model {}", instance_id));
+        Ok(())
+    }
+    
+    fn connection_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("connection_settings.json");
+        Ok(config_path)
+    }
+    
+    // Read the saved connection settings; none saved means the built-in defaults everywhere
+    pub fn read_connection_config(app_handle: &AppHandle) -> Result<ConnectionConfig, String> {
+        let config_path = connection_config_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(ConnectionConfig::default());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read connection settings file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse connection settings file: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn load_connection_settings(app_handle: tauri::AppHandle) -> Result<ConnectionConfig, String> {
+        read_connection_config(&app_handle)
+    }
+    
+    // Save timeouts, proxy, CA bundle and user-agent, for all providers and per provider.
+    // Every combination is checked first, so a bad proxy URL or CA file is caught here.
+    #[tauri::command]
+    async fn save_connection_settings(app_handle: tauri::AppHandle, config: ConnectionConfig) -> Result<(), String> {
+        config.defaults.build_client(HeaderMap::new()).map_err(|e| e.to_string())?;
+        for (provider, overrides) in &config.providers {
+            config.defaults.merged_with(overrides).build_client(HeaderMap::new())
+            .map_err(|e| format!("{}: {}", provider, e))?;
+        }
+        
+        let config_path = connection_config_path(&app_handle)?;
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize connection settings: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write connection settings file: {}", e))?;
+        
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", "Connection settings saved successfully");
+        Ok(())
+    }
+    
+    fn model_preferences_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("model_preferences.json");
+        Ok(config_path)
+    }
+    
+    // Read the saved model preferences; none saved means models are picked from each provider's list
+    pub fn read_model_preferences(app_handle: &AppHandle) -> Result<ModelPreferences, String> {
+        let config_path = model_preferences_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(ModelPreferences::default());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read model preferences file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse model preferences file: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn load_model_preferences(app_handle: tauri::AppHandle) -> Result<ModelPreferences, String> {
+        read_model_preferences(&app_handle)
+    }
+    
+    // Save the default model and the preferred model of each provider. transform_text uses the
+    // requested model, else the provider's preferred one, else the default, else the best chat
+    // model the provider lists.
+    #[tauri::command]
+    async fn save_model_preferences(app_handle: tauri::AppHandle, preferences: ModelPreferences) -> Result<(), String> {
+        let config_path = model_preferences_path(&app_handle)?;
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&preferences)
+        .map_err(|e| format!("Failed to serialize model preferences: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write model preferences file: {}", e))?;
+        
+        emit_console_message(&app_handle, "info", "Model preferences saved successfully");
+        Ok(())
+    }
+    
+    fn model_catalog_overrides_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("model_catalog_overrides.json");
+        Ok(config_path)
+    }
+    
+    // Read the user's additions and corrections to the bundled model catalog
+    pub fn read_model_catalog_overrides(app_handle: &AppHandle) -> Result<Vec<CatalogEntry>, String> {
+        let config_path = model_catalog_overrides_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read model catalog overrides file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse model catalog overrides file: {}", e))
+    }
+    
+    // The model catalog as list_models applies it, overrides merged in
+    #[tauri::command]
+    async fn get_model_catalog() -> Result<CatalogFile, String> {
+        let catalog = ModelCatalog::global();
+        Ok(CatalogFile {
+            version: catalog.version(),
+            updated: None,
+            models: catalog.entries(),
+        })
+    }
+    
+    #[tauri::command]
+    async fn load_model_catalog_overrides(app_handle: tauri::AppHandle) -> Result<Vec<CatalogEntry>, String> {
+        read_model_catalog_overrides(&app_handle)
+    }
+    
+    // Save catalog overrides, e.g. the real context window of a local model, and apply them right away
+    #[tauri::command]
+    async fn save_model_catalog_overrides(app_handle: tauri::AppHandle, overrides: Vec<CatalogEntry>) -> Result<(), String> {
+        if let Some(entry) = overrides.iter().find(|entry| entry.id.trim().is_empty()) {
+            return Err(format!("Every catalog override needs a model id: {:?}", entry));
+        }
+        
+        let config_path = model_catalog_overrides_path(&app_handle)?;
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&overrides)
+        .map_err(|e| format!("Failed to serialize model catalog overrides: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write model catalog overrides file: {}", e))?;
+        
+        ModelCatalog::install(ModelCatalog::with_overrides(overrides));
+        invalidate_model_cache(&app_handle);
+        emit_console_message(&app_handle, "info", "Model catalog overrides saved successfully");
+        Ok(())
+    }
+    
+    fn fallback_chain_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+        let mut config_path: PathBuf = app_handle.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        
+        config_path.push("config");
+        config_path.push("fallback_chain.json");
+        Ok(config_path)
+    }
+    
+    // Read the saved fallback chain, in the order providers are tried
+    pub fn read_fallback_chain(app_handle: &AppHandle) -> Result<Vec<FallbackLinkConfig>, String> {
+        let config_path = fallback_chain_path(app_handle)?;
+        
+        if !config_path.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let contents = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read fallback chain file: {}", e))?;
+        
+        serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse fallback chain file: {}", e))
+    }
+    
+    #[tauri::command]
+    async fn load_fallback_chain(app_handle: tauri::AppHandle) -> Result<Vec<FallbackLinkConfig>, String> {
+        read_fallback_chain(&app_handle)
+    }
+    
+    // Save the fallback chain, e.g. OpenAI, then LM Studio, then Ollama
+    #[tauri::command]
+    async fn save_fallback_chain(
+        app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        links: Vec<FallbackLinkConfig>,
+    ) -> Result<(), String> {
+        for link in &links {
+            registry.descriptor(&link.provider).map_err(|e| e.to_string())?;
+        }
+        
+        let config_path = fallback_chain_path(&app_handle)?;
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        
+        let contents = serde_json::to_string_pretty(&links)
+        .map_err(|e| format!("Failed to serialize fallback chain: {}", e))?;
+        fs::write(&config_path, contents).map_err(|e| format!("Failed to write fallback chain file: {}", e))?;
+        
+        emit_console_message(&app_handle, "info", "Fallback chain saved successfully");
+        Ok(())
     }
     
     // Updated command to get API key (frontend can call this to check if API key is set)
@@ -207,35 +1224,47 @@ async fn greet(name: &str,
 
 
     #[tauri::command]
+    #[allow(clippy::too_many_arguments)] // Tauri maps each argument to a field of the invoke payload
     async fn transform_text(
         app_handle: tauri::AppHandle,
+        registry: State<'_, ProviderRegistry>,
+        in_flight: State<'_, InFlightRequests>,
         request: TransformRequest,
-        provider_type: Option<String>, // e.g. "OpenAI", "LMStudio", "Ollama"
+        provider_type: Option<String>, // A registered provider id, e.g. "OpenAI", "Ollama" (see list_providers), or "Fallback"
         model_name: Option<String>,
         system_prompt: Option<String>, 
-    ) -> Result<String, String> {
-        // Choose provider type (default to OpenAI)
-        let provider_type = match provider_type.as_deref() {
-            Some("LMStudio") => ProviderType::LMStudio,
-            Some("Ollama") => ProviderType::Ollama,
-            _ => ProviderType::OpenAI,
-        };
+        endpoint: Option<String>, // Name of a saved endpoint, when provider_type is "OpenAICompatible"
+        request_id: Option<String>, // Chosen by the caller so it can cancel_transform before we answer
+    ) -> Result<TransformResponse, CommandError> {
+        let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let in_flight_request = in_flight.start(&request_id)?;
 
-        // Get provider config (API key or URL)
-        let config = match provider_type {
-            ProviderType::OpenAI => crate::get_openai_api_key(&app_handle)?,
-            ProviderType::LMStudio => "http://localhost:1234/v1/".to_string(), // Or load from settings
-            ProviderType::Ollama => "http://localhost:11434".to_string(),      // Or load from settings
+        // "Fallback" runs the saved chain; anything else is a registered provider id (default OpenAI)
+        let provider_id = provider_type.as_deref().unwrap_or("OpenAI");
+        let provider = if provider_id == "Fallback" {
+            let mut chain = FallbackProvider::default();
+            for link in read_fallback_chain(&app_handle)? {
+                match build_provider(&app_handle, &registry, &link.provider, link.endpoint.as_deref()) {
+                    Ok(link_provider) => chain.push(FallbackLink {
+                        provider: link_provider,
+                        model: link.model,
+                        model_map: link.model_map,
+                    }),
+                    Err(e) => emit_console_message(&app_handle, "warn", &format!("Skipping {} in the fallback chain: {}", link.provider, e)),
+                }
+            }
+            if chain.is_empty() {
+                return Err("No usable providers in the fallback chain. Please set it up in settings".to_string().into());
+            }
+            chain
+        } else {
+            FallbackProvider::single(build_provider(&app_handle, &registry, provider_id, endpoint.as_deref())?)
         };
 
-        // Create provider
-        let mut provider = create_provider(provider_type, &config);
-
-        // Set preferred model if provided
-        if let Some(model_name_str) = &model_name {
-            provider.set_preferred_inference_model(model_name_str.clone()).ok();
-        }
-
+        // Without a model_name, each provider's preferred model or the user's default is used,
+        // as long as the provider serves it; failing that, the best chat model it lists
+        let default_model = read_model_preferences(&app_handle)?.default_model;
+        let provider = provider.with_resolver(ModelResolver::new(default_model));
         
         // Use provided system prompt or a sensible default
         let system_prompt = system_prompt.unwrap_or_else(|| {
@@ -256,24 +1285,50 @@ async fn greet(name: &str,
             }
         ];
 
+        // Temperature and length are left to the model's saved options and the provider's defaults
         let chat_request = ChatCompletionRequest {
             messages,
-            model: model_name.unwrap_or_else(|| "gpt-4.1-nano-2025-04-14".to_string()),
-            temperature: Some(0.7),
-            max_tokens: Some(1024),
+            model: model_name.unwrap_or_default(),
+            temperature: None,
+            max_tokens: None,
             stream: false,
+            ..Default::default()
+        };
+
+        // Call the provider, falling back along the chain if there is one
+        // Cancelling drops the provider call, and with it the HTTP request
+        let answer = match in_flight_request.run(provider.complete(&chat_request)).await {
+            Ok(answer) => answer,
+            Err(AIProviderError::Cancelled) => {
+                emit_console_message(&app_handle, "info", &format!("Transform {} cancelled", request_id));
+                return Err(AIProviderError::Cancelled.into());
+            },
+            Err(e) => return Err(e.into()),
         };
 
-        // Call the provider
-        let response = provider.create_chat_completion(&chat_request).await
-            .map_err(|e| format!("LLM error: {}", e))?;
+        for attempt in &answer.attempts {
+            emit_console_message(&app_handle, "warn", &format!("{} ({}) failed: {}", attempt.provider, attempt.model, attempt.error));
+        }
 
-        // Return the first choice's content
-        let output = response.choices.get(0)
+        // Return the first choice's content, and who wrote it
+        let text = answer.response.choices.first()
             .map(|c| c.message.content.clone())
             .unwrap_or_default();
 
-        Ok(output)
+        Ok(TransformResponse {
+            text,
+            provider: answer.provider,
+            model: answer.model,
+            resolved_model: answer.resolved,
+            request_id,
+        })
+    }
+    
+    // Stop a running transform_text; it then fails with kind "Cancelled". Returns false when
+    // no transform with this id is running, e.g. because it already finished.
+    #[tauri::command]
+    fn cancel_transform(in_flight: State<'_, InFlightRequests>, request_id: String) -> bool {
+        in_flight.cancel(&request_id)
     }
     
     
@@ -282,6 +1337,9 @@ async fn greet(name: &str,
     pub fn run() {
         tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(ProviderRegistry::with_builtin_providers())
+        .manage(InFlightRequests::new())
+        .manage(ModelCache::default())
         .setup(|app| {
             // Initialize the logger
             env_logger::init();
@@ -289,15 +1347,53 @@ async fn greet(name: &str,
             // Load environment variables from .env file
             dotenv().ok();
             info!("Starting Side Hustler...");
+            // Apply the user's model catalog overrides; a broken file leaves the bundled catalog as is
+            match read_model_catalog_overrides(app.handle()) {
+                Ok(overrides) => ModelCatalog::install(ModelCatalog::with_overrides(overrides)),
+                Err(e) => warn!("Ignoring model catalog overrides: {}", e),
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             greet, 
             transform_text, 
-            list_openai_models,
+            cancel_transform,
+            list_providers,
+            load_fallback_chain,
+            save_fallback_chain,
+            load_connection_settings,
+            save_connection_settings,
+            load_model_preferences,
+            save_model_preferences,
+            check_provider_health,
+            check_providers_health,
+            discover_local_servers,
+            use_local_server,
+            pull_ollama_model,
+            cancel_ollama_pull,
+            delete_ollama_model,
+            copy_ollama_model,
+            get_ollama_disk_usage,
+            load_lm_studio_model,
+            unload_lm_studio_model,
+            get_model_catalog,
+            load_model_catalog_overrides,
+            save_model_catalog_overrides,
+            list_all_models,
             save_api_key,
             load_api_key,
-            get_api_key
+            get_api_key,
+            save_provider_api_key,
+            load_ollama_model_options,
+            save_ollama_model_options,
+            delete_ollama_model_options,
+            load_provider_settings,
+            save_provider_settings,
+            list_openai_compatible_endpoints,
+            save_openai_compatible_endpoint,
+            delete_openai_compatible_endpoint,
+            load_azure_openai_config,
+            save_azure_openai_config
             ])
             .run(tauri::generate_context!())
             .expect("error while running tauri application");
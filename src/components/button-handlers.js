@@ -1,6 +1,8 @@
 const { invoke } = window.__TAURI__.core;
 import yaml from 'js-yaml';
 import { logToConsole } from './Console.js';
+import { describeAIError } from '../utils/ai-errors.js';
+import { invokeTransform, isCancelled } from '../utils/transforms.js';
 
 /**
  * Formats JSON output from the LLM for display in EditorB.
@@ -66,18 +68,22 @@ export function setupActionButtonHandlers(getEditorAText, editorB) {
     const montaigneSystemPrompt = prompts.montaigne;
 
     try {
-      const result = await invoke('transform_text', {
+      const result = await invokeTransform({
         request: { text },
         providerType: 'OpenAI',
         modelName: 'gpt-4.1-nano-2025-04-14',
         systemPrompt: montaigneSystemPrompt,
       });
-      setEditorBContent(result);
+      setEditorBContent(result.text);
       setStatusBarMessage('Transformation complete.');
     } catch (err) {
-      setStatusBarMessage('Transformation failed: ' + err);
+      if (isCancelled(err)) {
+        setStatusBarMessage('Transformation cancelled.');
+        return;
+      }
+      setStatusBarMessage('Transformation failed: ' + describeAIError(err));
       console.error('Transformation error:', err);
-      logToConsole('error', `Transformation error: ${err.message}`);
+      logToConsole('error', `Transformation error: ${describeAIError(err)}`);
     }
   };
 
@@ -93,18 +99,22 @@ export function setupActionButtonHandlers(getEditorAText, editorB) {
     const systemPrompt = fillNamedPrompt(simplifyPrompt, { grade: 8, count: 3 });
 
     try {
-      const result = await invoke('transform_text', {
+      const result = await invokeTransform({
         request: { text },
         providerType: 'OpenAI',
         modelName: 'gpt-4.1-nano-2025-04-14',
         systemPrompt,
       });
-      setEditorBContent(result);
+      setEditorBContent(result.text);
       setStatusBarMessage('Simplification complete.');
     } catch (err) {
-      setStatusBarMessage('Simplification failed: ' + err);
+      if (isCancelled(err)) {
+        setStatusBarMessage('Simplification cancelled.');
+        return;
+      }
+      setStatusBarMessage('Simplification failed: ' + describeAIError(err));
       console.error('Simplification error:', err);
-      logToConsole('error', `Simplification error: ${err.message}`);
+      logToConsole('error', `Simplification error: ${describeAIError(err)}`);
     }
   };
 
@@ -116,18 +126,22 @@ export function setupActionButtonHandlers(getEditorAText, editorB) {
     const prompts = yaml.load(full_text);
 
     try {
-      const result = await invoke('transform_text', {
+      const result = await invokeTransform({
         request: { text },
         providerType: 'OpenAI',
         modelName: 'gpt-4.1-nano-2025-04-14',
         systemPrompt: prompts.post,
       });
-      setEditorBContent(result);
+      setEditorBContent(result.text);
       setStatusBarMessage('Blog post transformation complete.');
     } catch (err) {
-      setStatusBarMessage('Blog post transformation failed: ' + err);
+      if (isCancelled(err)) {
+        setStatusBarMessage('Blog post transformation cancelled.');
+        return;
+      }
+      setStatusBarMessage('Blog post transformation failed: ' + describeAIError(err));
       console.error('Blog post transformation error:', err);
-      logToConsole('error', `Blog post transformation error: ${err.message}`);
+      logToConsole('error', `Blog post transformation error: ${describeAIError(err)}`);
     }
   };
 }
@@ -127,6 +127,9 @@ document.head.appendChild(style);
 import { mountEditorB, getEditorBInstance } from './components/EditorB.js'
 import { mountConsole, logToConsole } from './components/Console.js'
 import { listen } from '@tauri-apps/api/event'
+import { describeAIError } from './utils/ai-errors.js'
+import { invokeTransform, cancelCurrentTransform, isCancelled } from './utils/transforms.js'
+import { checkProviders } from './utils/health.js'
 // import { invoke } from '@tauri-apps/api/tauri';
 const { invoke } = window.__TAURI__.core;
 
@@ -462,6 +465,9 @@ async function main() {
     console.error("Error setting up event listeners:", error);
   }
 
+  // Runs after the console listener is up, so unreachable providers are reported there
+  checkProviders();
+
   // ... any other async code ...
 }
 
@@ -471,6 +477,11 @@ main();
 window.addEventListener('keydown', async (event) => {
     if (event.key === 'Escape') {
         console.log("Escape key pressed");
+        // Escape stops a transform that's still running
+        if (await cancelCurrentTransform()) {
+            window.setStatusBarMessage('Cancelling transformation...');
+            return;
+        }
         await invoke('greet', { name: 'World' });
     }
 });
@@ -488,7 +499,7 @@ async function runTransformation() {
 
   // Call the Rust backend
   try {
-    const result = await invoke('transform_text', {
+    const result = await invokeTransform({
       appHandle: null, // Tauri injects this automatically
       request: {
         text: "This is the input text to transform:\n\n"+editorAText,
@@ -499,9 +510,13 @@ async function runTransformation() {
     });
 
     // Set the result in EditorB
-    editorB.commands.setContent(result); // Or .setText(result) if plain text
-    window.setStatusBarMessage('Transformation complete.');
+    editorB.commands.setContent(result.text); // Or .setText(result.text) if plain text
+    window.setStatusBarMessage(`Transformation complete (${result.provider}, ${result.model}).`);
   } catch (err) {
-    window.setStatusBarMessage('Transformation failed: ' + err);
+    if (isCancelled(err)) {
+      window.setStatusBarMessage('Transformation cancelled.');
+      return;
+    }
+    window.setStatusBarMessage('Transformation failed: ' + describeAIError(err));
   }
 }
@@ -0,0 +1,22 @@
+// ai-errors.js: Turn errors from AI commands into something the user can act on
+
+// What to suggest for each kind of provider failure (see AIErrorKind in src-tauri/src/ai/traits.rs)
+const HINTS = {
+  Auth: 'Check the API key for this provider in settings.',
+  RateLimited: 'The provider is rate limiting requests; wait a moment and try again.',
+  QuotaExhausted: 'The account is out of credit or over its quota.',
+  ContextLengthExceeded: 'The text is too long for this model; shorten it or pick a model with a larger context window.',
+  ContentFiltered: "The provider's content filter blocked this request.",
+  ModelNotFound: 'The selected model is not available from this provider.',
+  Network: 'The provider could not be reached; check the connection or that the local server is running.',
+  Timeout: 'The provider took too long to answer.',
+};
+
+// Command errors are either plain strings or { message, kind, retryable, details }
+export function describeAIError(err) {
+  if (err && typeof err === 'object' && 'kind' in err) {
+    const hint = HINTS[err.kind];
+    return hint ? `${err.message} ${hint}` : err.message;
+  }
+  return String(err?.message ?? err);
+}
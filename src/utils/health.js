@@ -0,0 +1,18 @@
+// health.js: Probe providers up front, so a server that's down shows up before the first transform
+const { invoke } = window.__TAURI__.core;
+
+// Check the configured providers and look for local servers. The backend reports what it
+// finds to the console; the results come back for anything that wants to show more.
+export async function checkProviders() {
+  const [providers, localServers] = await Promise.all([
+    invoke('check_providers_health').catch(() => []),
+    invoke('discover_local_servers').catch(() => []),
+  ]);
+  return { providers, localServers };
+}
+
+// Use a server from checkProviders' localServers for transforms. Resolves to the endpoint
+// name to pass along with server.provider_id, for servers saved as OpenAI compatible endpoints.
+export function useLocalServer(server) {
+  return invoke('use_local_server', { kind: server.kind, baseUrl: server.base_url });
+}
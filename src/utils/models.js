@@ -0,0 +1,31 @@
+// models.js: Every configured provider's models, for the model picker
+const { invoke } = window.__TAURI__.core;
+import { listen } from '@tauri-apps/api/event'
+
+// Models from all providers, each with provider_id, endpoint and available. Answers from the
+// backend's cache; pass refresh to wait for a fresh listing.
+export function listAllModels({ refresh = false } = {}) {
+  return invoke('list_all_models', { refresh });
+}
+
+// Call onUpdate with the new list whenever a background refresh finishes. Resolves to the
+// function that stops listening.
+export function onModelsUpdated(onUpdate) {
+  return listen('models-updated', (event) => onUpdate(event.payload));
+}
+
+// Models that can run a transform. Unknown capabilities count, as local servers don't always say.
+export function transformModels(models) {
+  return models.filter((model) =>
+    model.capabilities.length === 0 || model.capabilities.includes('ChatCompletion'));
+}
+
+// The default model and each provider's preferred model ({ default_model, providers }), used
+// when a transform doesn't name a model
+export function loadModelPreferences() {
+  return invoke('load_model_preferences');
+}
+
+export function saveModelPreferences(preferences) {
+  return invoke('save_model_preferences', { preferences });
+}
@@ -0,0 +1,35 @@
+// ollama.js: Install and remove Ollama models from the app instead of a terminal
+const { invoke } = window.__TAURI__.core;
+import { listen } from '@tauri-apps/api/event'
+
+// Pull a model, calling onProgress with { model_name, status, digest, total, completed } as it
+// downloads. Resolves once the model is installed; rejects with kind "Cancelled" if cancelled.
+export async function pullModel(modelName, onProgress) {
+  const unlisten = await listen('ollama-pull-progress', (event) => {
+    if (event.payload.model_name === modelName) {
+      onProgress?.(event.payload);
+    }
+  });
+  try {
+    await invoke('pull_ollama_model', { modelName });
+  } finally {
+    unlisten();
+  }
+}
+
+export function cancelPull(modelName) {
+  return invoke('cancel_ollama_pull', { modelName });
+}
+
+export function deleteModel(modelName) {
+  return invoke('delete_ollama_model', { modelName });
+}
+
+export function copyModel(source, destination) {
+  return invoke('copy_ollama_model', { source, destination });
+}
+
+// Installed models with their sizes, largest first, and the total in bytes
+export function diskUsage() {
+  return invoke('get_ollama_disk_usage');
+}
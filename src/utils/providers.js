@@ -0,0 +1,17 @@
+// providers.js: The registered providers and their saved settings
+const { invoke } = window.__TAURI__.core;
+
+// Every provider transform_text can use, each with the config schema its settings follow
+export function listProviders() {
+  return invoke('list_providers');
+}
+
+// A provider's saved settings, e.g. { base_url } for LM Studio and Ollama
+export function loadProviderSettings(provider) {
+  return invoke('load_provider_settings', { provider });
+}
+
+// Replace a provider's saved settings. API keys are saved separately, with save_provider_api_key.
+export function saveProviderSettings(provider, settings) {
+  return invoke('save_provider_settings', { provider, settings });
+}
@@ -0,0 +1,29 @@
+// transforms.js: Run transform_text so the running transform can be cancelled
+const { invoke } = window.__TAURI__.core;
+
+let currentTransformId = null;
+
+// Invoke transform_text under a fresh request id, which cancelCurrentTransform can stop
+export async function invokeTransform(args) {
+  const requestId = crypto.randomUUID();
+  currentTransformId = requestId;
+  try {
+    return await invoke('transform_text', { ...args, requestId });
+  } finally {
+    if (currentTransformId === requestId) {
+      currentTransformId = null;
+    }
+  }
+}
+
+// Cancel the most recent transform; resolves to false when none is running
+export async function cancelCurrentTransform() {
+  if (!currentTransformId) {
+    return false;
+  }
+  return invoke('cancel_transform', { requestId: currentTransformId });
+}
+
+export function isCancelled(err) {
+  return err?.kind === 'Cancelled';
+}